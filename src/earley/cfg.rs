@@ -0,0 +1,187 @@
+use rustc_hash::FxHashMap;
+
+use super::error::EarleyError;
+use crate::rules::grammar::Grammar;
+use crate::rules::literal::Literal;
+use crate::rules::literal::LiteralContent;
+use crate::rules::rule::RulePieceContent;
+use crate::rules::rule::RuleRef;
+use crate::spec_parser::rule_piece::Repetition;
+
+pub type SymbolRef = usize;
+
+/// A nonterminal of the flattened grammar. A rule appears once
+/// for every context it's used in: inside of an atomic rule,
+/// where nothing gets skipped, or not
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Symbol {
+    /// the main rule, followed by what's skipped after it
+    Start,
+    Rule {
+        rule: RuleRef,
+        atomic: bool,
+    },
+    /// one or more times a rule, `together` when written with `+`
+    Repeat {
+        rule: RuleRef,
+        atomic: bool,
+        together: bool,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Element<'g> {
+    Symbol(SymbolRef),
+    Literal {
+        literal: &'g Literal<'g>,
+        repeated: bool,
+        name: &'g str,
+    },
+    /// whatever the skip rules consume here, maybe nothing
+    Skip,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Production<'g> {
+    pub lhs: SymbolRef,
+    pub rhs: Vec<Element<'g>>,
+}
+
+/// A `Grammar` turned into a plain context free grammar, with
+/// repetitions as left recursive rules and the skip rules made
+/// explicit, so it can be parsed by the earley parser
+#[derive(Debug)]
+pub struct Cfg<'g> {
+    pub gram: &'g Grammar<'g>,
+    pub symbols: Vec<Symbol>,
+    pub productions: Vec<Production<'g>>,
+    /// the productions of every symbol
+    pub by_lhs: Vec<Vec<usize>>,
+}
+
+impl<'g> Cfg<'g> {
+    /// The start symbol is always the first one
+    pub const START: SymbolRef = 0;
+
+    pub fn from_grammar(gram: &'g Grammar<'g>) -> Result<Self, EarleyError> {
+        // precedence climbing has no place in a plain grammar
+        if !gram.operators.is_empty() {
+            return Err(EarleyError::Unsupported("operators"));
+        }
+
+        let mut cfg = Self {
+            gram,
+            symbols: Vec::new(),
+            productions: Vec::new(),
+            by_lhs: Vec::new(),
+        };
+        let mut ids = FxHashMap::default();
+        let mut to_expand = vec![cfg.symbol(&mut ids, Symbol::Start)];
+
+        while let Some(id) = to_expand.pop() {
+            let len = cfg.symbols.len();
+            let rhs_list = cfg.expand(&mut ids, cfg.symbols[id]);
+            to_expand.extend(len..cfg.symbols.len());
+
+            for rhs in rhs_list {
+                cfg.by_lhs[id].push(cfg.productions.len());
+                cfg.productions.push(Production { lhs: id, rhs });
+            }
+        }
+
+        Ok(cfg)
+    }
+
+    fn symbol(&mut self, ids: &mut FxHashMap<Symbol, SymbolRef>, symbol: Symbol) -> SymbolRef {
+        *ids.entry(symbol).or_insert_with(|| {
+            self.symbols.push(symbol);
+            self.by_lhs.push(Vec::new());
+            self.symbols.len() - 1
+        })
+    }
+
+    /// The right hand sides of the productions of `symbol`
+    fn expand(
+        &mut self,
+        ids: &mut FxHashMap<Symbol, SymbolRef>,
+        symbol: Symbol,
+    ) -> Vec<Vec<Element<'g>>> {
+        let gram = self.gram;
+        let skips = !gram.skip.is_empty();
+
+        match symbol {
+            Symbol::Start => {
+                let main = Symbol::Rule {
+                    rule: 0,
+                    atomic: false,
+                };
+                let mut rhs = vec![Element::Symbol(self.symbol(ids, main))];
+
+                if skips {
+                    rhs.push(Element::Skip);
+                }
+
+                vec![rhs]
+            }
+            Symbol::Rule { rule, atomic } => {
+                let rule = &gram.rules[&rule];
+                let atomic = atomic || gram.is_atomic(rule.name);
+
+                let mut alternatives = Vec::new();
+                for list in &rule.rule.0 {
+                    let mut rhs = Vec::new();
+
+                    for piece in &list.0 {
+                        if skips && !atomic {
+                            rhs.push(Element::Skip);
+                        }
+
+                        let repeated = piece.repetition != Repetition::Single;
+                        let element = match &piece.content {
+                            RulePieceContent::Literal(literal) => Element::Literal {
+                                literal,
+                                repeated,
+                                name: match literal.content {
+                                    LiteralContent::Str(string) => string,
+                                    LiteralContent::Range { .. } => "char range",
+                                },
+                            },
+                            RulePieceContent::Rule(rule) if repeated => {
+                                let together = piece.repetition == Repetition::RepeatTogether;
+                                let repeat = Symbol::Repeat {
+                                    rule: *rule,
+                                    atomic,
+                                    together,
+                                };
+                                Element::Symbol(self.symbol(ids, repeat))
+                            }
+                            RulePieceContent::Rule(rule) => {
+                                let called = Symbol::Rule {
+                                    rule: *rule,
+                                    atomic,
+                                };
+                                Element::Symbol(self.symbol(ids, called))
+                            }
+                        };
+
+                        rhs.push(element);
+                    }
+
+                    alternatives.push(rhs);
+                }
+
+                alternatives
+            }
+            Symbol::Repeat { rule, atomic, .. } => {
+                // <repeat>: <rule> | <repeat> <rule>;
+                let repeat = self.symbol(ids, symbol);
+                let once = self.symbol(ids, Symbol::Rule { rule, atomic });
+
+                vec![
+                    vec![Element::Symbol(once)],
+                    vec![Element::Symbol(repeat), Element::Symbol(once)],
+                ]
+            }
+        }
+    }
+}
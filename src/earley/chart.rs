@@ -0,0 +1,254 @@
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use super::cfg::Cfg;
+use super::cfg::Element;
+use super::cfg::Symbol;
+use super::cfg::SymbolRef;
+use super::error::EarleyError;
+use crate::nodes::input::Input;
+use crate::nodes::node::Node;
+use crate::nodes::options::ParseOptions;
+use crate::nodes::state::ParseState;
+use crate::rules::literal::LiteralContent;
+use crate::utils::is_word_char;
+
+/// A production with how much of it was recognized, started at `origin`
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct Item {
+    production: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// Finds where the terminals of the grammar end, starting at some
+/// position of the input. Positions are byte offsets
+pub struct Scanner<'g, 'i> {
+    input: &'i str,
+    state: ParseState<'g, 'i>,
+    skipped: FxHashMap<usize, usize>,
+}
+
+impl<'g, 'i> Scanner<'g, 'i> {
+    /// The limits in `options` count every item the recognizer
+    /// goes through, and the rules tried by the skip rules
+    pub fn new(cfg: &Cfg<'g>, input: &'i str, options: &ParseOptions) -> Self {
+        Self {
+            input,
+            state: ParseState::with_input(cfg.gram, Input::new(cfg.gram, input), options),
+            skipped: FxHashMap::default(),
+        }
+    }
+
+    /// Every position where `element` can end if it starts at
+    /// `start`. Repeated literals can end in more than one place
+    pub fn ends(&mut self, element: &Element<'g>, start: usize) -> Vec<usize> {
+        let input = &self.input[start..];
+
+        match *element {
+            Element::Symbol(_) => unreachable!("symbols aren't terminals"),
+            // the skip rules always skip as much as they can
+            Element::Skip => {
+                let state = &mut self.state;
+                let len = *self
+                    .skipped
+                    .entry(start)
+                    .or_insert_with(|| Node::skipped_len(state, input));
+                vec![start + len]
+            }
+            Element::Literal {
+                literal, repeated, ..
+            } => {
+                let mut ends = Vec::new();
+                let mut len = 0;
+
+                loop {
+                    let rest = &input[len..];
+                    let matched = match literal.content {
+                        LiteralContent::Str(string) => {
+                            rest.starts_with(string).then_some(string.len())
+                        }
+                        LiteralContent::Range { from, to } => rest
+                            .chars()
+                            .next()
+                            .filter(|c| (from..=to).contains(c))
+                            .map(char::len_utf8),
+                    };
+
+                    match matched {
+                        Some(matched) => len += matched,
+                        None => break,
+                    }
+                    ends.push(start + len);
+
+                    // an empty string would be repeated forever
+                    if !repeated || matched == Some(0) {
+                        break;
+                    }
+                }
+
+                // a keyword can't be the beginning of a longer word
+                if let LiteralContent::Str(word) = literal.content {
+                    let gram = self.state.gram;
+                    if !repeated && gram.is_keyword(word) {
+                        ends.retain(|end| !self.input[*end..].starts_with(is_word_char));
+                    }
+                }
+
+                ends
+            }
+        }
+    }
+}
+
+/// The result of recognizing the input: every symbol that matched
+/// some part of it, with where it started and where it ended
+#[derive(Debug, Default)]
+pub struct Chart {
+    /// by symbol and start, every position where it can end
+    pub completed: FxHashMap<(SymbolRef, usize), Vec<usize>>,
+    /// the last position any item got to
+    pub furthest: usize,
+}
+
+impl Chart {
+    pub fn ends(&self, symbol: SymbolRef, start: usize) -> &[usize] {
+        self.completed
+            .get(&(symbol, start))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn accepts(&self, input: &str) -> bool {
+        self.ends(Cfg::START, 0).contains(&input.len())
+    }
+
+    /// Runs the earley recognizer over the whole input
+    pub fn recognize<'g, 'i>(
+        cfg: &Cfg<'g>,
+        scanner: &mut Scanner<'g, 'i>,
+        input: &'i str,
+    ) -> Result<Self, EarleyError> {
+        let gram = cfg.gram;
+        let mut chart = Self::default();
+
+        let mut sets: Vec<Vec<Item>> = vec![Vec::new(); input.len() + 1];
+        let mut seen: Vec<FxHashSet<Item>> = vec![FxHashSet::default(); input.len() + 1];
+        let mut predicted: Vec<FxHashSet<SymbolRef>> = vec![FxHashSet::default(); input.len() + 1];
+        // symbols that matched nothing at some position
+        let mut empty: Vec<FxHashSet<SymbolRef>> = vec![FxHashSet::default(); input.len() + 1];
+        let mut completed = FxHashSet::default();
+
+        let mut add = |sets: &mut Vec<Vec<Item>>, position: usize, item: Item| {
+            if seen[position].insert(item) {
+                sets[position].push(item);
+            }
+        };
+
+        for &production in &cfg.by_lhs[Cfg::START] {
+            add(
+                &mut sets,
+                0,
+                Item {
+                    production,
+                    dot: 0,
+                    origin: 0,
+                },
+            );
+        }
+
+        for position in 0..=input.len() {
+            if !sets[position].is_empty() {
+                chart.furthest = position;
+            }
+
+            let mut i = 0;
+            while i < sets[position].len() {
+                let item = sets[position][i];
+                i += 1;
+                scanner.state.count_step().map_err(EarleyError::Stopped)?;
+
+                let production = &cfg.productions[item.production];
+                let advanced = Item {
+                    dot: item.dot + 1,
+                    ..item
+                };
+
+                let element = match production.rhs.get(item.dot) {
+                    Some(element) => element,
+                    None => {
+                        let (lhs, origin) = (production.lhs, item.origin);
+
+                        // identifiers can't match a keyword
+                        if let Symbol::Rule { rule, .. } = cfg.symbols[lhs] {
+                            let name = gram.rules[&rule].name;
+                            if gram.identifiers.contains(name)
+                                && gram.is_keyword(&input[origin..position])
+                            {
+                                continue;
+                            }
+                        }
+
+                        if completed.insert((lhs, origin, position)) {
+                            chart
+                                .completed
+                                .entry((lhs, origin))
+                                .or_default()
+                                .push(position);
+                        }
+                        if origin == position {
+                            empty[position].insert(lhs);
+                        }
+
+                        let waiting: Vec<_> = sets[origin]
+                            .iter()
+                            .filter(|waiting| {
+                                cfg.productions[waiting.production].rhs.get(waiting.dot)
+                                    == Some(&Element::Symbol(lhs))
+                            })
+                            .map(|waiting| Item {
+                                dot: waiting.dot + 1,
+                                ..*waiting
+                            })
+                            .collect();
+
+                        for waiting in waiting {
+                            add(&mut sets, position, waiting);
+                        }
+                        continue;
+                    }
+                };
+
+                match element {
+                    Element::Symbol(symbol) => {
+                        if predicted[position].insert(*symbol) {
+                            for &production in &cfg.by_lhs[*symbol] {
+                                add(
+                                    &mut sets,
+                                    position,
+                                    Item {
+                                        production,
+                                        dot: 0,
+                                        origin: position,
+                                    },
+                                );
+                            }
+                        }
+
+                        // it was already completed, and won't be again
+                        if empty[position].contains(symbol) {
+                            add(&mut sets, position, advanced);
+                        }
+                    }
+                    terminal => {
+                        for end in scanner.ends(terminal, position) {
+                            add(&mut sets, end, advanced);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(chart)
+    }
+}
@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+use crate::nodes::error::Limit;
+use crate::utils::Position;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EarleyError {
+    #[error("@{0} isn't supported by the earley parser")]
+    Unsupported(&'static str),
+    #[error("can't parse the input past {position}")]
+    NoParse { offset: usize, position: Position },
+    /// one of the limits in `ParseOptions` was reached
+    #[error("parse stopped: {0}")]
+    Stopped(Limit),
+}
@@ -0,0 +1,799 @@
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use super::cfg::Cfg;
+use super::cfg::Element;
+use super::cfg::Symbol;
+use super::cfg::SymbolRef;
+use super::chart::Chart;
+use super::chart::Scanner;
+use super::error::EarleyError;
+use crate::nodes::node::Node;
+use crate::nodes::node::NodeContent;
+use crate::nodes::options::ParseOptions;
+use crate::rules::grammar::Grammar;
+use crate::utils::Position;
+
+pub type ForestRef = usize;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ForestNode<'g> {
+    /// a rule, or a repetition of a rule, matching `start..end`,
+    /// with every way it can be split in children
+    Symbol {
+        symbol: Symbol,
+        start: usize,
+        end: usize,
+        families: Vec<Vec<ForestRef>>,
+    },
+    Terminal {
+        name: &'g str,
+        start: usize,
+        end: usize,
+    },
+    /// text consumed by the skip rules
+    Skipped { start: usize, end: usize },
+}
+
+/// Every way the input can be parsed by a grammar, sharing the
+/// parts the different parses have in common. Nodes can be part
+/// of cycles, when a rule can derive itself
+#[derive(Debug)]
+pub struct Forest<'g, 'i> {
+    pub gram: &'g Grammar<'g>,
+    pub input: &'i str,
+    pub nodes: Vec<ForestNode<'g>>,
+    pub root: ForestRef,
+}
+
+impl<'g, 'i> Forest<'g, 'i> {
+    /// Parses all of the input with an earley parser, that works with
+    /// any grammar, even ambiguous ones. `@choice` is ignored, since
+    /// every alternative that matches is kept
+    pub fn from_grammar(gram: &'g Grammar<'g>, input: &'i str) -> Result<Self, EarleyError> {
+        Self::from_grammar_with_options(gram, input, &ParseOptions::default())
+    }
+
+    /// Like `from_grammar`, with the limits of `options`. The
+    /// other options don't change how the forest is found
+    pub fn from_grammar_with_options(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> Result<Self, EarleyError> {
+        let cfg = Cfg::from_grammar(gram)?;
+        let mut scanner = Scanner::new(&cfg, input, options);
+        let chart = Chart::recognize(&cfg, &mut scanner, input)?;
+
+        if !chart.accepts(input) {
+            return Err(EarleyError::NoParse {
+                offset: chart.furthest,
+                position: Position::from_offset(input, chart.furthest),
+            });
+        }
+
+        let mut builder = Builder {
+            cfg: &cfg,
+            chart: &chart,
+            scanner,
+            nodes: Vec::new(),
+            symbols: FxHashMap::default(),
+            terminals: FxHashMap::default(),
+            splits: FxHashMap::default(),
+            unsplit: Vec::new(),
+        };
+        let root = builder.build(Cfg::START, 0, input.len());
+
+        Ok(Self {
+            gram,
+            input,
+            nodes: builder.nodes,
+            root,
+        })
+    }
+
+    /// How many different trees are in the forest, stopping at `u64::MAX`
+    pub fn count_trees(&self) -> u64 {
+        let mut memo = match self.has_cycles() {
+            true => None,
+            false => Some(FxHashMap::default()),
+        };
+
+        self.count(self.root, &mut memo)
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.count_trees() > 1
+    }
+
+    /// Every tree in the forest, built like `Node::from_grammar` would.
+    /// There can be a lot of them, see `count_trees`
+    pub fn trees(&self) -> Vec<Node<'g, 'i>> {
+        let mut trees: Vec<_> = self.expand(self.root).into_iter().flatten().collect();
+
+        for tree in &mut trees {
+            tree.locate(self.input);
+        }
+        trees
+    }
+
+    /// The ways a node can be built, each one as the nodes it adds to
+    /// the children of its parent. Symbols wait in a stack for their
+    /// children to be expanded, instead of recursing, since trees can
+    /// be as deep as the input is long
+    fn expand(&self, root: ForestRef) -> Vec<Vec<Node<'g, 'i>>> {
+        let mut path = FxHashSet::default();
+        let mut stack: Vec<Expanding<'g, 'i>> = Vec::new();
+        // what the last node expanded to, for the one under it in the stack
+        let mut expanded = self.start_expanding(root, &mut path, &mut stack);
+
+        while let Some(top) = stack.last_mut() {
+            if let Some(expanded) = expanded.take() {
+                top.expanded.push(expanded);
+            }
+            if let Some(child) = top.children.get(top.expanded.len()).copied() {
+                expanded = self.start_expanding(child, &mut path, &mut stack);
+                continue;
+            }
+
+            let top = stack.pop().expect("it's the top");
+            expanded = Some(self.finish_expanding(top, &mut path));
+        }
+
+        expanded.expect("the root was expanded")
+    }
+
+    /// The ways `node` can be built, if they're known without
+    /// expanding its children. Otherwise they get expanded in `stack`
+    fn start_expanding(
+        &self,
+        node: ForestRef,
+        path: &mut FxHashSet<ForestRef>,
+        stack: &mut Vec<Expanding<'g, 'i>>,
+    ) -> Option<Vec<Vec<Node<'g, 'i>>>> {
+        let (symbol, start, end, families) = match &self.nodes[node] {
+            ForestNode::Terminal { name, start, end } => {
+                let text = NodeContent::Literal(&self.input[*start..*end]);
+                return Some(vec![vec![Node::new(Some(name), text)]]);
+            }
+            ForestNode::Skipped { .. } => return Some(vec![vec![]]),
+            ForestNode::Symbol {
+                symbol,
+                start,
+                end,
+                families,
+            } => (*symbol, *start, *end, families),
+        };
+
+        // following a cycle would give infinite trees
+        if path.contains(&node) {
+            return Some(Vec::new());
+        }
+
+        let mut expanding = Expanding {
+            node,
+            children: Vec::new(),
+            expanded: Vec::new(),
+            chain: Vec::new(),
+            uses: FxHashMap::default(),
+        };
+
+        match symbol {
+            // the text is the same however it's split
+            Symbol::Repeat {
+                rule,
+                together: true,
+                ..
+            } => {
+                let text = NodeContent::Literal(&self.input[start..end]);
+                return Some(vec![vec![Node::new(
+                    Some(self.gram.rules[&rule].name),
+                    text,
+                )]]);
+            }
+            Symbol::Repeat { .. } => {
+                path.insert(node);
+                self.repeated(&mut expanding, path);
+            }
+            Symbol::Start | Symbol::Rule { .. } => {
+                path.insert(node);
+                expanding.children = families.concat();
+            }
+        }
+
+        stack.push(expanding);
+        None
+    }
+
+    /// The ways a node can be built, once its children were expanded
+    fn finish_expanding(
+        &self,
+        expanding: Expanding<'g, 'i>,
+        path: &mut FxHashSet<ForestRef>,
+    ) -> Vec<Vec<Node<'g, 'i>>> {
+        let Expanding {
+            node,
+            expanded,
+            chain,
+            mut uses,
+            ..
+        } = expanding;
+        let (symbol, families) = match &self.nodes[node] {
+            ForestNode::Symbol {
+                symbol, families, ..
+            } => (*symbol, families),
+            _ => unreachable!("only symbols get expanded"),
+        };
+        let mut expanded = expanded.into_iter();
+
+        let built = match symbol {
+            Symbol::Start => families
+                .iter()
+                .flat_map(|family| product(expanded.by_ref().take(family.len())))
+                .collect(),
+            Symbol::Rule { rule, .. } => families
+                .iter()
+                .flat_map(|family| product(expanded.by_ref().take(family.len())))
+                .map(|children| {
+                    vec![Node::new(
+                        Some(self.gram.rules[&rule].name),
+                        NodeContent::Cons(children.into_iter().map(Arc::new).collect()),
+                    )]
+                })
+                .collect(),
+            Symbol::Repeat { rule, .. } => {
+                // `node` was already in the path
+                for repeat in &chain {
+                    if *repeat != node {
+                        path.remove(repeat);
+                    }
+                }
+
+                self.build_repeated(node, &chain, &mut uses, expanded)
+                    .into_iter()
+                    .map(|found| {
+                        vec![Node::new(
+                            Some(self.gram.rules[&rule].name),
+                            NodeContent::Cons(found.into_iter().map(Arc::new).collect()),
+                        )]
+                    })
+                    .collect()
+            }
+        };
+
+        path.remove(&node);
+        built
+    }
+
+    /// Finds what a repetition is built from, as the items it repeats
+    fn repeated(&self, expanding: &mut Expanding<'g, 'i>, path: &mut FxHashSet<ForestRef>) {
+        let families = |node: ForestRef| match &self.nodes[node] {
+            ForestNode::Symbol { families, .. } => families,
+            _ => unreachable!("repetitions are symbols"),
+        };
+        let end = |node: ForestRef| match &self.nodes[node] {
+            ForestNode::Symbol { end, .. } => *end,
+            _ => unreachable!("repetitions are symbols"),
+        };
+
+        // <repeat>: <rule> | <repeat> <rule>; there's a shorter repetition
+        // for every item, so they're collected first and built from the
+        // shortest, instead of recursing. `uses` is how many longer
+        // ones are still going to need them
+        let node = expanding.node;
+        let mut chain = vec![node];
+        let uses = &mut expanding.uses;
+        let mut to_visit = vec![node];
+        while let Some(next) = to_visit.pop() {
+            for family in families(next) {
+                if let [repeat, _] = family.as_slice() {
+                    if path.contains(repeat) {
+                        continue;
+                    }
+                    let used = uses.entry(*repeat).or_default();
+                    if *used == 0 {
+                        chain.push(*repeat);
+                        to_visit.push(*repeat);
+                    }
+                    *used += 1;
+                }
+            }
+        }
+
+        // the shorter repetitions start at the same place and end earlier
+        chain.sort_by_key(|repeat| end(*repeat));
+        path.extend(&chain);
+
+        // the items of every repetition get expanded, shortest first
+        for repeat in &chain {
+            for family in families(*repeat) {
+                match family.as_slice() {
+                    [last] => expanding.children.push(*last),
+                    [shorter, last] if uses.contains_key(shorter) => {
+                        expanding.children.push(*last);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        expanding.chain = chain;
+    }
+
+    /// The ways a repetition can be built, as the list of what was
+    /// repeated, from its chain and what its items expanded to
+    fn build_repeated(
+        &self,
+        node: ForestRef,
+        chain: &[ForestRef],
+        uses: &mut FxHashMap<ForestRef, usize>,
+        mut expanded: impl Iterator<Item = Vec<Vec<Node<'g, 'i>>>>,
+    ) -> Vec<Vec<Node<'g, 'i>>> {
+        let mut built: FxHashMap<ForestRef, Vec<Vec<Node<'g, 'i>>>> = FxHashMap::default();
+
+        for repeat in chain.iter().copied() {
+            let families = match &self.nodes[repeat] {
+                ForestNode::Symbol { families, .. } => families,
+                _ => unreachable!("repetitions are symbols"),
+            };
+            let mut found = Vec::new();
+
+            for family in families {
+                let before = match family.as_slice() {
+                    [_] => vec![Vec::new()],
+                    [shorter, _] if uses.contains_key(shorter) => {
+                        let left = uses.get_mut(shorter).expect("counted with the chain");
+                        *left -= 1;
+                        let before = match *left {
+                            0 => built.remove(shorter),
+                            _ => built.get(shorter).cloned(),
+                        };
+                        before.unwrap_or_default()
+                    }
+                    _ => continue,
+                };
+
+                let lasts = expanded.next().expect("expanded with the chain");
+                for mut before in before {
+                    // the last one can take the list, instead of copying it
+                    for (i, last) in lasts.iter().enumerate() {
+                        match i + 1 == lasts.len() {
+                            true => {
+                                before.extend_from_slice(last);
+                                found.push(std::mem::take(&mut before));
+                            }
+                            false => found.push([before.as_slice(), last].concat()),
+                        }
+                    }
+                }
+            }
+
+            built.insert(repeat, found);
+        }
+
+        built.remove(&node).unwrap_or_default()
+    }
+
+    fn count(&self, root: ForestRef, memo: &mut Option<FxHashMap<ForestRef, u64>>) -> u64 {
+        let mut path = FxHashSet::default();
+        let mut stack = Vec::new();
+        // what the last node counted had, for the one under it in the stack
+        let mut counted = self.start_count(root, &mut path, memo, &mut stack);
+
+        while let Some(top) = stack.last_mut() {
+            let families = match &self.nodes[top.node] {
+                ForestNode::Symbol { families, .. } => families,
+                _ => unreachable!("only symbols get counted"),
+            };
+
+            if let Some(count) = counted.take() {
+                top.product = top.product.saturating_mul(count);
+                top.child += 1;
+            }
+            if let Some(child) = families[top.family].get(top.child).copied() {
+                counted = self.start_count(child, &mut path, memo, &mut stack);
+                continue;
+            }
+
+            top.count = top.count.saturating_add(top.product);
+            top.family += 1;
+            top.child = 0;
+            top.product = 1;
+            if top.family < families.len() {
+                continue;
+            }
+
+            let Counting { node, count, .. } = stack.pop().expect("it's the top");
+            path.remove(&node);
+            if let Some(memo) = memo {
+                memo.insert(node, count);
+            }
+            counted = Some(count);
+        }
+
+        counted.expect("the root was counted")
+    }
+
+    /// How many trees `node` has, if it's known without counting
+    /// its children. Otherwise they get counted in `stack`
+    fn start_count(
+        &self,
+        node: ForestRef,
+        path: &mut FxHashSet<ForestRef>,
+        memo: &Option<FxHashMap<ForestRef, u64>>,
+        stack: &mut Vec<Counting>,
+    ) -> Option<u64> {
+        let families = match &self.nodes[node] {
+            ForestNode::Symbol {
+                symbol: Symbol::Repeat { together: true, .. },
+                ..
+            } => return Some(1),
+            ForestNode::Symbol { families, .. } => families,
+            _ => return Some(1),
+        };
+
+        if let Some(count) = memo.as_ref().and_then(|memo| memo.get(&node)) {
+            return Some(*count);
+        }
+        // following a cycle would count infinite trees
+        if families.is_empty() || path.contains(&node) {
+            return Some(0);
+        }
+
+        path.insert(node);
+        stack.push(Counting {
+            node,
+            family: 0,
+            child: 0,
+            product: 1,
+            count: 0,
+        });
+        None
+    }
+
+    fn has_cycles(&self) -> bool {
+        // 0 not visited, 1 being visited, 2 done
+        let mut marks = vec![0u8; self.nodes.len()];
+        let mut stack = vec![(self.root, 0)];
+
+        while let Some((node, child)) = stack.pop() {
+            let children: Vec<ForestRef> = match &self.nodes[node] {
+                ForestNode::Symbol { families, .. } => families.iter().flatten().copied().collect(),
+                _ => Vec::new(),
+            };
+
+            if child == 0 {
+                marks[node] = 1;
+            }
+
+            match children.get(child) {
+                Some(next) => {
+                    stack.push((node, child + 1));
+                    match marks[*next] {
+                        0 => stack.push((*next, 0)),
+                        1 => return true,
+                        _ => {}
+                    }
+                }
+                None => marks[node] = 2,
+            }
+        }
+
+        false
+    }
+}
+
+/// Every way of combining the trees of the children of a family,
+/// given what each of them expanded to
+fn product<'g, 'i>(
+    children: impl Iterator<Item = Vec<Vec<Node<'g, 'i>>>>,
+) -> Vec<Vec<Node<'g, 'i>>> {
+    let mut combined = vec![Vec::new()];
+
+    for expanded in children {
+        combined = combined
+            .iter()
+            .flat_map(|before| {
+                expanded
+                    .iter()
+                    .map(move |nodes| [before.as_slice(), nodes].concat())
+            })
+            .collect();
+    }
+
+    combined
+}
+
+/// A symbol being expanded by `Forest::expand`: the children it's
+/// waiting for, and what the ones before them expanded to. For
+/// repetitions, also the shorter ones they're built on
+struct Expanding<'g, 'i> {
+    node: ForestRef,
+    children: Vec<ForestRef>,
+    expanded: Vec<Vec<Vec<Node<'g, 'i>>>>,
+    chain: Vec<ForestRef>,
+    uses: FxHashMap<ForestRef, usize>,
+}
+
+/// A node being counted by `Forest::count`: the family and the
+/// child in it that's next, the product of the children before it
+/// and the sum of the families before it
+struct Counting {
+    node: ForestRef,
+    family: usize,
+    child: usize,
+    product: u64,
+    count: u64,
+}
+
+/// Builds the forest from the chart, from the root down, so
+/// only what's part of a full parse ends up in it
+struct Builder<'c, 'g, 'i> {
+    cfg: &'c Cfg<'g>,
+    chart: &'c Chart,
+    scanner: Scanner<'g, 'i>,
+    nodes: Vec<ForestNode<'g>>,
+    symbols: FxHashMap<(SymbolRef, usize, usize), ForestRef>,
+    terminals: FxHashMap<(Option<&'g str>, usize, usize), ForestRef>,
+    /// by production, position in it, start and end
+    splits: FxHashMap<(usize, usize, usize, usize), Vec<Vec<ForestRef>>>,
+    /// symbols whose families haven't been found yet
+    unsplit: Vec<(SymbolRef, ForestRef)>,
+}
+
+impl<'c, 'g, 'i> Builder<'c, 'g, 'i> {
+    /// The node of `symbol` and everything under it. Nodes get their
+    /// children one after the other instead of recursing, since a
+    /// repetition is as deep as it's long
+    fn build(&mut self, symbol: SymbolRef, start: usize, end: usize) -> ForestRef {
+        let root = self.symbol(symbol, start, end);
+
+        while let Some((symbol, node)) = self.unsplit.pop() {
+            let (start, end) = match &self.nodes[node] {
+                ForestNode::Symbol { start, end, .. } => (*start, *end),
+                _ => unreachable!("only symbols have families"),
+            };
+
+            let mut found = Vec::new();
+            for &production in &self.cfg.by_lhs[symbol] {
+                found.extend(self.splits(production, 0, start, end));
+            }
+
+            if let ForestNode::Symbol { families, .. } = &mut self.nodes[node] {
+                *families = found;
+            }
+        }
+
+        root
+    }
+
+    /// The node of `symbol`, without its families, see `build`
+    fn symbol(&mut self, symbol: SymbolRef, start: usize, end: usize) -> ForestRef {
+        if let Some(node) = self.symbols.get(&(symbol, start, end)) {
+            return *node;
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(ForestNode::Symbol {
+            symbol: self.cfg.symbols[symbol],
+            start,
+            end,
+            families: Vec::new(),
+        });
+        self.symbols.insert((symbol, start, end), node);
+        self.unsplit.push((symbol, node));
+        node
+    }
+
+    fn terminal(&mut self, name: Option<&'g str>, start: usize, end: usize) -> ForestRef {
+        let nodes = &mut self.nodes;
+
+        *self.terminals.entry((name, start, end)).or_insert_with(|| {
+            nodes.push(match name {
+                Some(name) => ForestNode::Terminal { name, start, end },
+                None => ForestNode::Skipped { start, end },
+            });
+            nodes.len() - 1
+        })
+    }
+
+    /// Every way the elements of a production from `dot` onwards
+    /// can match `start..end`, as the nodes they match
+    fn splits(
+        &mut self,
+        production: usize,
+        dot: usize,
+        start: usize,
+        end: usize,
+    ) -> Vec<Vec<ForestRef>> {
+        // the splits from the next element on are found first
+        let mut to_visit = vec![(dot, start)];
+
+        while let Some(&(dot, start)) = to_visit.last() {
+            let key = (production, dot, start, end);
+            if self.splits.contains_key(&key) {
+                to_visit.pop();
+                continue;
+            }
+
+            let rhs = &self.cfg.productions[production].rhs;
+            let (element, next) = match (rhs.get(dot), rhs.get(dot + 1)) {
+                (Some(element), next) => (*element, next.copied()),
+                (None, _) => {
+                    let found = match start == end {
+                        true => vec![Vec::new()],
+                        false => Vec::new(),
+                    };
+                    self.splits.insert(key, found);
+                    to_visit.pop();
+                    continue;
+                }
+            };
+            let last = dot + 2 == rhs.len();
+
+            let ends: Vec<usize> = self
+                .ends(element, start)
+                .into_iter()
+                .filter(|middle| *middle == end || (*middle < end && next.is_some()))
+                .collect();
+
+            // the last two elements are matched here, or every
+            // place a repetition ends would get its own splits
+            if let (Some(next), true) = (next, last) {
+                let mut found = Vec::new();
+                for middle in ends {
+                    if self.matches(next, middle, end) {
+                        let node = self.node(element, start, middle);
+                        found.push(vec![node, self.node(next, middle, end)]);
+                    }
+                }
+
+                self.splits.insert(key, found);
+                to_visit.pop();
+                continue;
+            }
+
+            let missing: Vec<_> = ends
+                .iter()
+                .filter(|middle| {
+                    !self
+                        .splits
+                        .contains_key(&(production, dot + 1, **middle, end))
+                })
+                .map(|middle| (dot + 1, *middle))
+                .collect();
+            if !missing.is_empty() {
+                to_visit.extend(missing);
+                continue;
+            }
+            to_visit.pop();
+
+            let mut found = Vec::new();
+            for middle in ends {
+                let next = (production, dot + 1, middle, end);
+                if self.splits[&next].is_empty() {
+                    continue;
+                }
+
+                let node = self.node(element, start, middle);
+                for rest in &self.splits[&next] {
+                    found.push([&[node], rest.as_slice()].concat());
+                }
+            }
+
+            self.splits.insert(key, found);
+        }
+
+        self.splits[&(production, dot, start, end)].clone()
+    }
+
+    /// Every position where `element` can end if it starts at `start`
+    fn ends(&mut self, element: Element<'g>, start: usize) -> Vec<usize> {
+        match element {
+            Element::Symbol(symbol) => self.chart.ends(symbol, start).to_vec(),
+            terminal => self.scanner.ends(&terminal, start),
+        }
+    }
+
+    fn matches(&mut self, element: Element<'g>, start: usize, end: usize) -> bool {
+        match element {
+            Element::Symbol(symbol) => self.chart.ends(symbol, start).contains(&end),
+            terminal => self.scanner.ends(&terminal, start).contains(&end),
+        }
+    }
+
+    fn node(&mut self, element: Element<'g>, start: usize, end: usize) -> ForestRef {
+        match element {
+            Element::Symbol(symbol) => self.symbol(symbol, start, end),
+            Element::Literal { name, .. } => self.terminal(Some(name), start, end),
+            Element::Skip => self.terminal(None, start, end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::error::Limit;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node as _;
+
+    fn grammar<'a>(spec: &'a str) -> Grammar<'a> {
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        Grammar::try_from(&spec).unwrap()
+    }
+
+    #[test]
+    fn ambiguous() {
+        let grammar = grammar("<somma>: <somma> '+' <somma> | <somma> '*' <somma> | [0-9];");
+
+        let forest = Forest::from_grammar(&grammar, "1+2*3+4").unwrap();
+        assert_eq!(forest.count_trees(), 5);
+
+        let trees = forest.trees();
+        assert_eq!(trees.len(), 5);
+        for tree in &trees {
+            assert_eq!(tree.name, Some("somma"));
+        }
+
+        let got = Forest::from_grammar(&grammar, "1+2*").unwrap_err();
+        assert_eq!(
+            got.to_string(),
+            "can't parse the input past line 1, column 5"
+        );
+    }
+
+    #[test]
+    fn toki_pona() {
+        let spec = r#"
+<sentence>: <adverb> <space> 'la' <space> <simple_sentence> | <simple_sentence>;
+<adverb>: <noun_phrase> | <simple_sentence>;
+<subject>: <noun_phrase>;
+<simple_sentence>: <subject> <space> 'li' <space> <predicate>;
+<noun_phrase>: <word> | <word> <space_and_word>;
+<word>: 'mi' | 'sina' | 'ona' | 'pona' | 'toki' | 'nimi' | 'li'
+    | 'mani' | 'lape' | 'tomo' | 'tawa' | 'jan' | 'ma' | <loan_word>;
+<loan_word>: [A-Z] [a-z]+;
+<space_and_word>: <space>+ <word>;
+<space>: ' '+;
+<predicate>: <noun_phrase>;"#
+            .trim();
+        let grammar = grammar(spec);
+
+        // "li" is also a word, so it can be part of the subject
+        let input = "jan li li pona";
+        let forest = Forest::from_grammar(&grammar, input).unwrap();
+        let trees = forest.trees();
+        assert_eq!(trees.len(), forest.count_trees() as usize);
+        assert_eq!(trees.len(), 2);
+
+        let (longest, _) = Node::from_grammar(&grammar, input).unwrap();
+        assert!(trees.contains(&longest));
+    }
+
+    #[test]
+    fn stopped() {
+        let grammar = grammar("<somma>: <somma> '+' <somma> | [0-9];");
+        let options = ParseOptions {
+            max_steps: Some(10),
+            ..Default::default()
+        };
+
+        let got = Forest::from_grammar_with_options(&grammar, "1+2+3+4", &options).unwrap_err();
+        assert_eq!(got, EarleyError::Stopped(Limit::Steps(10)));
+    }
+
+    #[test]
+    fn long_repetition() {
+        let grammar = grammar("<parole>: <parola>*; <parola>: 'ciao ';");
+
+        let input = "ciao ".repeat(2_000);
+        let forest = Forest::from_grammar(&grammar, &input).unwrap();
+        assert_eq!(forest.count_trees(), 1);
+
+        let trees = forest.trees();
+        let (longest, _) = Node::from_grammar(&grammar, &input).unwrap();
+        assert_eq!(trees, vec![longest]);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod cfg;
+pub mod chart;
+pub mod error;
+pub mod forest;
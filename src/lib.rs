@@ -0,0 +1,6 @@
+pub mod earley;
+pub mod nodes;
+pub mod rules;
+pub mod spec_parser;
+pub mod structures;
+pub mod utils;
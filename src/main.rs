@@ -2,24 +2,61 @@ use std::fmt::Display;
 #[allow(unused_imports)]
 use std::fs;
 use std::io::{stdin, Read};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
 #[allow(unused_imports)]
 use std::{env, process};
 
-mod nodes;
-mod rules;
-mod spec_parser;
-mod structures;
-mod utils;
-
-use nodes::node::Node;
-use rules::grammar::Grammar;
-use spec_parser::grammar::Grammar as SpecGrammar;
-use spec_parser::node::Node as NodeTrait;
+use elevators::earley::forest::Forest;
+use elevators::nodes::node::Node;
+use elevators::nodes::options::ParseOptions;
+use elevators::nodes::stream::Records;
+use elevators::rules::analysis::ambiguity::ambiguities;
+use elevators::rules::analysis::left_recursion::left_recursion_warnings;
+use elevators::rules::analysis::reachability::lint;
+use elevators::rules::analysis::sets::grammar_sets;
+use elevators::rules::grammar::Grammar;
+use elevators::spec_parser::grammar::Grammar as SpecGrammar;
+use elevators::spec_parser::node::Node as NodeTrait;
 
 use eerie::Files;
 
+const USAGE: &str = "usage: elevators <file> [--sets] [--lint] [--no-memo] [--memo-stats] [--forest] [--partial] [--recover] [--spans] [--bytes=FILE]
+                       [--max-steps=N] [--timeout=MS] [--max-depth=N] [--records=RULE]
+                       [--trace[=json]] [--trace-rules=RULE,...]
+
+    --sets          print which rules are nullable, and their FIRST and FOLLOW sets
+    --lint          also warn about alternatives that are how other ones start
+    --no-memo       don't remember what rules parsed, uses less memory but can be slower
+    --memo-stats    print how much the memo table was used
+    --forest        print every way the whole input can be parsed, one tree per line
+    --partial       don't fail if there's input left after the main rule
+    --recover       go on after errors, skipping to the next @sync token
+    --spans         print where every node is in the input
+    --bytes=FILE    parse the bytes in FILE instead of the input file, for grammars with @bytes
+    --max-steps=N   give up after trying rules N times
+    --timeout=MS    give up after MS milliseconds
+    --max-depth=N   let rules nest at most N deep
+    --records=RULE  parse RULE again and again from standard input instead of the input file,
+                    printing each match as soon as it's parsed
+    --trace         print every rule and alternative tried, where, and how much it matched
+    --trace=json    the same, as JSON, one line per try
+    --trace-rules=RULE,...
+                    only trace these rules";
+
 fn main() {
-    let filename = env::args().nth(1).or_crash(Some("file name needed"));
+    let args: Vec<String> = env::args().skip(1).collect();
+    let has_flag = |flag: &str| args.iter().any(|arg| arg == flag);
+    let flag_number = |flag: &str| {
+        args.iter()
+            .find_map(|arg| arg.strip_prefix(flag)?.strip_prefix('='))
+            .map(|number| number.parse::<u64>().or_crash(Some(USAGE)))
+    };
+
+    let filename = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .or_crash(Some(USAGE));
     let file = fs::read_to_string(filename).or_crash(Some("can't read file"));
 
     let (files, _) = Files::from_str(&file).or_crash(None);
@@ -31,18 +68,129 @@ fn main() {
         .or_crash(Some("can't find grammar file"))
         .content;
 
+    // records are read from standard input, so they don't need one
     let input_file = files
         .0
         .iter()
         .find(|file| file.name.starts_with("input"))
-        .or_crash(Some("can't find input file"))
-        .content;
+        .map(|file| file.content);
 
     let (spec, _) = SpecGrammar::parse_len(grammar_file).or_crash(None);
-    let grammar = Grammar::try_from(&spec).or_crash(None);
 
-    let (tree, _) = Node::from_grammar(&grammar, input_file).or_crash(None);
-    println!("{}", tree);
+    let report = lint(&spec);
+    if !report.is_empty() {
+        eprintln!("{}", report);
+    }
+    if report.has_errors() {
+        process::exit(1);
+    }
+
+    let grammar = Grammar::from_linted(&spec, &report).or_crash(None);
+
+    for warning in left_recursion_warnings(&grammar) {
+        eprintln!("warning: {}", warning);
+    }
+
+    for ambiguity in ambiguities(&grammar) {
+        if ambiguity.decided_by_order() || has_flag("--lint") {
+            eprintln!("warning: {}", ambiguity);
+        }
+    }
+
+    if has_flag("--sets") {
+        println!("{}", grammar_sets(&grammar).report(&grammar));
+        return;
+    }
+
+    let options = ParseOptions {
+        memoize: !has_flag("--no-memo"),
+        strict: !has_flag("--partial"),
+        max_steps: flag_number("--max-steps").map(|steps| steps as usize),
+        deadline: flag_number("--timeout").map(|ms| Instant::now() + Duration::from_millis(ms)),
+        max_depth: flag_number("--max-depth").map(|depth| depth as usize),
+        ..Default::default()
+    };
+
+    if let Some(name) = args.iter().find_map(|arg| arg.strip_prefix("--records=")) {
+        let rule = grammar.get_named(name).or_crash(Some("can't find the records rule"));
+        let mut records = Records::new(&grammar, rule, stdin().lock());
+        records.options = options;
+
+        records
+            .for_each(|tree, _| {
+                print_tree(tree, has_flag("--spans"));
+                ControlFlow::Continue(())
+            })
+            .or_crash(None);
+        return;
+    }
+
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--bytes=")) {
+        let input = fs::read(path).or_crash(Some("can't read file"));
+        let (tree, _) = Node::from_bytes(&grammar, &input, &options).or_crash(None);
+        print_tree(&tree, has_flag("--spans"));
+        return;
+    }
+
+    let input_file = input_file.or_crash(Some("can't find input file"));
+
+    if has_flag("--forest") {
+        let forest =
+            Forest::from_grammar_with_options(&grammar, input_file, &options).or_crash(None);
+        eprintln!("trees: {}", forest.count_trees());
+
+        for tree in forest.trees() {
+            print_tree(&tree, has_flag("--spans"));
+        }
+        return;
+    }
+
+    if has_flag("--recover") {
+        let (tree, errors) =
+            Node::from_grammar_recovering_with_options(&grammar, input_file, &options);
+        print_tree(&tree, has_flag("--spans"));
+
+        for error in &errors {
+            eprintln!("error: {}", error);
+        }
+        if !errors.is_empty() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let trace_json = args.iter().any(|arg| arg == "--trace=json");
+    if has_flag("--trace") || trace_json {
+        let rules = args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--trace-rules="))
+            .map_or(Vec::new(), |rules| rules.split(',').map(String::from).collect());
+
+        let (parsed, trace) = Node::from_grammar_traced(&grammar, input_file, &options, rules);
+        match trace_json {
+            true => eprint!("{}", trace.json_lines()),
+            false => eprint!("{}", trace),
+        }
+
+        let (tree, _) = parsed.or_crash(None);
+        print_tree(&tree, has_flag("--spans"));
+        return;
+    }
+
+    let (tree, _, stats) =
+        Node::from_grammar_with_stats(&grammar, input_file, &options).or_crash(None);
+    print_tree(&tree, has_flag("--spans"));
+
+    if has_flag("--memo-stats") {
+        eprintln!("{}", stats);
+    }
+}
+
+fn print_tree(tree: &Node, spans: bool) {
+    match spans {
+        true => println!("{}", tree.with_spans()),
+        false => println!("{}", tree),
+    }
 }
 
 #[allow(dead_code)]
@@ -2,43 +2,140 @@ use std::fmt;
 
 use super::node::Node;
 use super::node::NodeContent;
+use super::span::Span;
 
-impl<'grammar, 'input> fmt::Display for Node<'grammar, 'input> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.name {
-            Some(name) => write!(f, "{{\"name\": \"{}\", \"content\": [", name)?,
-            None => write!(f, "[")?,
-        }
+/// Shows a node like its `Display` does, with
+/// the span of every node too
+pub struct WithSpans<'n, 'grammar, 'input>(&'n Node<'grammar, 'input>);
+
+impl<'grammar, 'input> Node<'grammar, 'input> {
+    pub fn with_spans(&self) -> WithSpans<'_, 'grammar, 'input> {
+        WithSpans(self)
+    }
+
+    /// Writes the tree with a stack of what's left to write, instead
+    /// of recursing, since it can be as deep as the input is long
+    fn write(&self, f: &mut fmt::Formatter<'_>, spans: bool) -> fmt::Result {
+        let mut to_write = vec![ToWrite::Node(self)];
 
-        write!(f, "{}", self.content)?;
+        while let Some(next) = to_write.pop() {
+            let node = match next {
+                ToWrite::Node(node) => node,
+                ToWrite::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+            };
+
+            if let NodeContent::Error(string) = node.content {
+                write!(f, "{{\"error\": {}", json_string(string))?;
+                if spans {
+                    write!(f, ", \"span\": {}", node.span)?;
+                }
+                write!(f, "}}")?;
+                continue;
+            }
+
+            match (node.name, spans) {
+                (Some(name), false) => write!(f, "{{\"name\": \"{}\", \"content\": [", name)?,
+                (Some(name), true) => write!(
+                    f,
+                    "{{\"name\": \"{}\", \"span\": {}, \"content\": [",
+                    name, node.span
+                )?,
+                (None, false) => write!(f, "[")?,
+                (None, true) => write!(f, "{{\"span\": {}, \"content\": [", node.span)?,
+            }
 
-        match self.name {
-            Some(_) => write!(f, "]}}")?,
-            None => write!(f, "]")?,
+            match (node.name, spans) {
+                (None, false) => to_write.push(ToWrite::Text("]")),
+                _ => to_write.push(ToWrite::Text("]}")),
+            }
+
+            match &node.content {
+                NodeContent::Cons(list) => {
+                    for (i, child) in list.iter().enumerate().rev() {
+                        to_write.push(ToWrite::Node(child));
+                        if i > 0 {
+                            to_write.push(ToWrite::Text(", "));
+                        }
+                    }
+                }
+                content => content.write(f, spans)?,
+            }
         }
 
         Ok(())
     }
 }
 
+/// What's left to write of a tree, see `Node::write`
+enum ToWrite<'n, 'grammar, 'input> {
+    Node(&'n Node<'grammar, 'input>),
+    Text(&'static str),
+}
+
+/// `text` as a JSON string. Error text is whatever was in the
+/// input, so it can have quotes and backslashes in it
+fn json_string(text: &str) -> String {
+    serde_json::to_string(text).expect("a string is always valid JSON")
+}
+
+impl<'grammar, 'input> fmt::Display for Node<'grammar, 'input> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+impl<'n, 'grammar, 'input> fmt::Display for WithSpans<'n, 'grammar, 'input> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write(f, true)
+    }
+}
+
 impl<'grammar, 'input> fmt::Display for NodeContent<'grammar, 'input> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"start\": {}, \"end\": {}, \"start_line\": {}, \"start_column\": {}, \"end_line\": {}, \"end_column\": {}}}",
+            self.start,
+            self.end,
+            self.start_position.line,
+            self.start_position.column,
+            self.end_position.line,
+            self.end_position.column
+        )
+    }
+}
+
+impl<'grammar, 'input> NodeContent<'grammar, 'input> {
+    fn write(&self, f: &mut fmt::Formatter<'_>, spans: bool) -> fmt::Result {
         match self {
             Self::Literal(string) => write!(f, "\"{}\"", string)?,
+            Self::Error(string) => write!(f, "{{\"error\": {}}}", json_string(string))?,
+            Self::Bytes(bytes) => {
+                let hex: Vec<_> = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                write!(f, "\"{}\"", hex.join(" "))?
+            }
             Self::Cons(list) => {
-                write!(f, "{}", list[0])?;
-                for node in list.iter().skip(1) {
-                    write!(f, ", ")?;
-                    write!(f, "{}", node)?;
+                for (i, node) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    node.write(f, spans)?;
                 }
             }
         }
 
         Ok(())
     }
-}
 
-impl<'grammar, 'input> NodeContent<'grammar, 'input> {
     // fn primitive(&self) -> Option<&'input str> {
     //     match self {
     //         Self::Literal(string) => Some(string),
@@ -46,3 +143,22 @@ impl<'grammar, 'input> NodeContent<'grammar, 'input> {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_text() {
+        let error = Node::new(None, NodeContent::Error("a = \"b\\"));
+        let node = Node::new(Some("riga"), NodeContent::Cons(vec![error.into()]));
+
+        let shown = node.to_string();
+        assert_eq!(
+            shown,
+            r#"{"name": "riga", "content": [{"error": "a = \"b\\"}]}"#
+        );
+        assert!(serde_json::from_str::<serde_json::Value>(&shown).is_ok());
+        assert!(serde_json::from_str::<serde_json::Value>(&node.with_spans().to_string()).is_ok());
+    }
+}
@@ -1,6 +1,10 @@
+use std::fmt;
+
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+use crate::utils::Position;
+
+#[derive(Debug, Error, Clone)]
 pub enum ParseError<'grammar, 'input> {
     #[error("while parsing {parsing}: expected \"{expected}\", found \"{got}\"")]
     Expected {
@@ -8,4 +12,76 @@ pub enum ParseError<'grammar, 'input> {
         expected: String,
         got: &'input str,
     },
+    #[error("while parsing {parsing}: \"{keyword}\" is a reserved keyword")]
+    Reserved {
+        parsing: &'grammar str,
+        keyword: &'input str,
+    },
+    #[error("while parsing {parsing}: alternatives {first} and {second} both match \"{text}\"")]
+    Ambiguous {
+        parsing: &'grammar str,
+        first: usize,
+        second: usize,
+        text: &'input str,
+    },
+    #[error(
+        "while parsing {parsing}: expected the end of the input at {position}, found \"{got}\""
+    )]
+    Unconsumed {
+        parsing: &'grammar str,
+        /// where the input that wasn't parsed starts
+        offset: usize,
+        position: Position,
+        got: &'input str,
+    },
+    /// the furthest the parser got, with everything that could have
+    /// come next there, and the line it's in with a caret under it
+    #[error("{position}: expected {}\n{snippet}", one_of(.expected))]
+    Unexpected {
+        offset: usize,
+        position: Position,
+        expected: Vec<String>,
+        snippet: String,
+    },
+    /// the leaves of trees parsed as bytes aren't
+    /// text, so they can't be moved in the new input
+    #[error("a tree of bytes can't be reparsed")]
+    ReparsedBytes,
+    /// one of the limits in `ParseOptions` was reached
+    #[error("parse stopped: {0}")]
+    Stopped(Limit),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Steps(usize),
+    Depth(usize),
+    Deadline,
+    Cancelled,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Steps(max) => write!(f, "rules were tried more than {} times", max),
+            Self::Depth(max) => write!(f, "rules were nested more than {} deep", max),
+            Self::Deadline => write!(f, "the deadline passed"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+fn one_of(expected: &[String]) -> String {
+    match expected {
+        [single] => single.clone(),
+        _ => format!("one of: {}", expected.join(" ")),
+    }
+}
+
+impl<'grammar, 'input> ParseError<'grammar, 'input> {
+    /// Errors that stop the whole parse, instead of
+    /// making the parser try something else
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Ambiguous { .. } | Self::Stopped(_))
+    }
 }
@@ -0,0 +1,347 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use super::error::ParseError;
+use super::memo::MemoKey;
+use super::node::Node;
+use super::node::NodeContent;
+use super::span::Span;
+use crate::rules::grammar::Grammar;
+
+/// A change to the input: the bytes in `range`
+/// get replaced with `text`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Edit<'a> {
+    pub range: Range<usize>,
+    pub text: &'a str,
+}
+
+impl<'a> Edit<'a> {
+    /// The input after the edit
+    pub fn apply(&self, input: &str) -> String {
+        format!(
+            "{}{}{}",
+            &input[..self.range.start],
+            self.text,
+            &input[self.range.end..]
+        )
+    }
+
+    /// Where an offset after the edit ends up
+    fn moved(&self, offset: usize) -> usize {
+        offset - self.range.end + self.range.start + self.text.len()
+    }
+
+    /// Where the text of `span` starts after the edit, if
+    /// it's all before the edit or all after it
+    fn moved_start(&self, span: &Span) -> Option<usize> {
+        match (span.end <= self.range.start, span.start >= self.range.end) {
+            (true, _) => Some(span.start),
+            (_, true) => Some(self.moved(span.start)),
+            _ => None,
+        }
+    }
+}
+
+type Reusable<'g, 'i> = FxHashMap<MemoKey<'g>, (Arc<Node<'g, 'i>>, usize)>;
+
+/// A node moved in the new input, if it comes after the edit
+type Moved<'g, 'i> = Result<Option<Arc<Node<'g, 'i>>>, ParseError<'g, 'i>>;
+
+impl<'g, 'o> Node<'g, 'o> {
+    /// The rule nodes the edit doesn't change, moved in the new input
+    /// and keyed like the memo table. What a rule matches only depends
+    /// on the input that follows it, so the ones after the edit stay
+    /// the same, and so do the ones before it that didn't look as far
+    /// as it. Every node is moved once, and shared by the nodes around it
+    pub fn reusable<'i>(
+        &self,
+        gram: &Grammar<'g>,
+        edit: &Edit,
+        input: &'i str,
+    ) -> Result<Reusable<'g, 'i>, ParseError<'g, 'i>> {
+        let mut reusable = FxHashMap::default();
+        self.find_reusable(gram, edit, input, false, &mut reusable)?;
+        Ok(reusable)
+    }
+
+    /// Returns the node moved in the new input. `atomic`
+    /// is whether it's inside of an atomic rule
+    fn find_reusable<'i>(
+        &self,
+        gram: &Grammar<'g>,
+        edit: &Edit,
+        input: &'i str,
+        atomic: bool,
+        reusable: &mut Reusable<'g, 'i>,
+    ) -> Moved<'g, 'i> {
+        // literals, and rules repeated together, are only text
+        let children = match &self.content {
+            NodeContent::Cons(list) => list,
+            _ => return self.moved(edit, input),
+        };
+
+        let inner_atomic = atomic || self.name.is_some_and(|name| gram.is_atomic(name));
+        let mut moved = Vec::new();
+        for child in children {
+            moved.extend(child.find_reusable(gram, edit, input, inner_atomic, reusable)?);
+        }
+
+        let start = edit.moved_start(&self.span);
+        let node = start.map(|_| {
+            let mut node = Node::new(self.name, NodeContent::Cons(moved));
+            node.is_rule = self.is_rule;
+            node.looked = self.looked;
+            Arc::new(node)
+        });
+
+        // repetitions, and the operands of `@operators` rules, have the
+        // rule's name, but aren't parsed like the rule is
+        if self.is_rule && self.span.start < self.span.end {
+            let unchanged = self.span.start >= edit.range.end
+                || self.span.start + self.looked <= edit.range.start;
+            let start = start.or((self.span.start < edit.range.start).then_some(self.span.start));
+
+            // left recursive rules nest in themselves at the same place,
+            // and what they parse is the outermost one, that comes last
+            if let Some(start) = start {
+                let key = (self.name.unwrap(), input.len() - start, atomic);
+                match &node {
+                    Some(node) if unchanged => {
+                        reusable.insert(key, (node.clone(), self.span.end - self.span.start))
+                    }
+                    _ => reusable.remove(&key),
+                };
+            }
+        }
+
+        Ok(node)
+    }
+
+    /// The same leaf, with its text taken from the new input
+    fn moved<'i>(&self, edit: &Edit, input: &'i str) -> Moved<'g, 'i> {
+        if let NodeContent::Bytes(_) = self.content {
+            return Err(ParseError::ReparsedBytes);
+        }
+        let start = match edit.moved_start(&self.span) {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+
+        let text = &input[start..start + self.span.end - self.span.start];
+        let content = match &self.content {
+            NodeContent::Error(_) => NodeContent::Error(text),
+            _ => NodeContent::Literal(text),
+        };
+
+        Ok(Some(Arc::new(Node::new(self.name, content))))
+    }
+}
+
+/// Where `new` is different from `old`, as ranges of the new input.
+/// Lists of children are compared from both ends, so adding an
+/// item to a list only changes where the item is
+pub fn changed_ranges(old: &Node, new: &Node) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    diff(old, new, &mut ranges);
+
+    ranges.sort_by_key(|range| (range.start, range.end));
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+fn diff(old: &Node, new: &Node, ranges: &mut Vec<Range<usize>>) {
+    if same(old, new) {
+        return;
+    }
+
+    let (old_list, new_list) = match (&old.content, &new.content) {
+        (NodeContent::Cons(old_list), NodeContent::Cons(new_list)) if old.name == new.name => {
+            (old_list, new_list)
+        }
+        _ => {
+            ranges.push(new.span.start..new.span.end);
+            return;
+        }
+    };
+
+    let pairs = old_list.iter().zip(new_list);
+    let prefix = pairs.take_while(|(old, new)| same(old, new)).count();
+
+    let shortest = old_list.len().min(new_list.len()) - prefix;
+    let pairs = old_list.iter().rev().zip(new_list.iter().rev());
+    let suffix = pairs
+        .take(shortest)
+        .take_while(|(old, new)| same(old, new))
+        .count();
+
+    let old_middle = &old_list[prefix..old_list.len() - suffix];
+    let new_middle = &new_list[prefix..new_list.len() - suffix];
+
+    if old_middle.len() == new_middle.len() {
+        for (old, new) in old_middle.iter().zip(new_middle) {
+            diff(old, new, ranges);
+        }
+        return;
+    }
+
+    match (new_middle.first(), new_middle.last()) {
+        (Some(first), Some(last)) => ranges.push(first.span.start..last.span.end),
+        // something was removed, and nothing is left of it
+        _ => {
+            let at = match prefix {
+                0 => new.span.start,
+                _ => new_list[prefix - 1].span.end,
+            };
+            ranges.push(at..at);
+        }
+    }
+}
+
+/// Whether two nodes have the same names and text,
+/// wherever they are
+fn same(old: &Node, new: &Node) -> bool {
+    if old.name != new.name {
+        return false;
+    }
+
+    match (&old.content, &new.content) {
+        (NodeContent::Literal(old), NodeContent::Literal(new)) => old == new,
+        (NodeContent::Error(old), NodeContent::Error(new)) => old == new,
+        (NodeContent::Bytes(old), NodeContent::Bytes(new)) => old == new,
+        (NodeContent::Cons(old), NodeContent::Cons(new)) => {
+            old.len() == new.len() && old.iter().zip(new).all(|(old, new)| same(old, new))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::options::ParseOptions;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node as _;
+
+    #[test]
+    fn reparse() {
+        let spec = r#"
+<programma>: <istruzione>*;
+<istruzione>: <nome> '=' <valore> ';';
+<valore>: <valore> '+' <nome> | <nome>;
+<nome>: [a-z]+;
+<spazio>: ' '+;
+@skip <spazio>;
+@atomic <nome>;"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = "a = b; c = d + e; f = g;";
+        let (old, _) = Node::from_grammar(&grammar, input).unwrap();
+
+        let edits = [
+            (11..12, "xy", vec![(11, 13)]),
+            (16..16, " + h", vec![(11, 16), (19, 20)]),
+            (6..6, " i = j;", vec![(7, 13)]),
+            (23..23, "h", vec![(22, 24)]),
+            (24..24, " k = l;", vec![(25, 31)]),
+            (0..0, "", vec![]),
+        ];
+
+        for (range, text, expected) in edits {
+            let edit = Edit { range, text };
+            let edited = edit.apply(input);
+            let (node, changed) = Node::reparse(&grammar, &old, &edit, &edited).unwrap();
+
+            let (fresh, _) = Node::from_grammar(&grammar, &edited).unwrap();
+            assert_eq!(node, fresh);
+            let changed: Vec<_> = changed
+                .iter()
+                .map(|range| (range.start, range.end))
+                .collect();
+            assert_eq!(changed, expected);
+        }
+    }
+
+    #[test]
+    fn reused() {
+        let spec = r#"
+<programma>: <istruzione>*;
+<istruzione>: <nome> '=' <nome> ';';
+<nome>: [a-z]+;
+<spazio>: ' '+;
+@skip <spazio>;
+@atomic <nome>;"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = "a = b; ".repeat(100);
+        let (old, _) = Node::from_grammar(&grammar, &input).unwrap();
+
+        // at the start, and at the end of the input
+        let edits = [
+            (0..1, "c", 0..1),
+            (input.len()..input.len(), "d = e;", 700..706),
+        ];
+
+        for (range, text, expected) in edits {
+            let edit = Edit { range, text };
+            let edited = edit.apply(&input);
+
+            // parsing the statements around the edit again takes a
+            // lot more steps than finding them in the old tree
+            let options = ParseOptions {
+                max_steps: Some(600),
+                ..Default::default()
+            };
+            assert!(Node::from_grammar_with_options(&grammar, &edited, &options).is_err());
+
+            let (node, changed) =
+                Node::reparse_with_options(&grammar, &old, &edit, &edited, &options).unwrap();
+            assert_eq!(node, Node::from_grammar(&grammar, &edited).unwrap().0);
+            assert_eq!(changed, vec![expected]);
+        }
+    }
+
+    #[test]
+    fn rule_nodes() {
+        let spec = r#"
+<programma>: <istruzione>*;
+<istruzione>: <nome> '=' <x> ';';
+<x>: 'x';
+<nome>: [a-z]+;
+<spazio>: ' '+;
+@skip <spazio>;
+@atomic <nome>;
+@sync ';';"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = "a = x; b = = ; c = x;";
+        let (old, errors) = Node::from_grammar_recovering(&grammar, input);
+        assert_eq!(errors.len(), 1);
+
+        // the repetition has the name of its items, and an error
+        // node in it, but only the items are reused
+        let edit = Edit {
+            range: 0..0,
+            text: "",
+        };
+        let reusable = old.reusable(&grammar, &edit, input).unwrap();
+        assert_eq!(reusable[&("istruzione", input.len(), false)].1, 6);
+        assert_eq!(reusable[&("x", input.len() - 4, false)].1, 1);
+        assert!(reusable.values().all(|(node, _)| node.is_rule));
+    }
+}
@@ -0,0 +1,150 @@
+use super::node::NodeContent;
+use crate::rules::grammar::Grammar;
+use crate::utils;
+use crate::utils::take_n;
+use crate::utils::utf8_prefix;
+use crate::utils::Position;
+
+/// What gets parsed. The parser goes through both kinds of input as
+/// bytes, and only turns what it matched back into text for text.
+/// With bytes, the leaves of the tree are byte slices, and columns
+/// count bytes, with lines that end at 0x0A
+#[derive(Debug, Clone, Copy)]
+pub enum Input<'i> {
+    Text(&'i str),
+    Bytes(&'i [u8]),
+}
+
+impl<'i> From<&'i str> for Input<'i> {
+    fn from(text: &'i str) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl<'i> From<&'i [u8]> for Input<'i> {
+    fn from(bytes: &'i [u8]) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl<'i> Input<'i> {
+    /// The input for parsing `text` with `gram`: its bytes,
+    /// if the grammar matches bytes, see `@bytes`
+    pub fn new(gram: &Grammar, text: &'i str) -> Self {
+        match gram.bytes {
+            true => Self::Bytes(text.as_bytes()),
+            false => Self::Text(text),
+        }
+    }
+
+    pub fn bytes(&self) -> &'i [u8] {
+        match self {
+            Self::Text(text) => text.as_bytes(),
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Where `slice`, a piece of the input, starts in it
+    pub fn offset(&self, slice: &[u8]) -> usize {
+        slice.as_ptr() as usize - self.bytes().as_ptr() as usize
+    }
+
+    /// The text of `slice`, a piece of the input. Bytes are
+    /// cut before the first one that isn't part of UTF-8 text
+    pub fn text(&self, slice: &'i [u8]) -> &'i str {
+        match self {
+            Self::Text(text) => {
+                let start = self.offset(slice);
+                &text[start..start + slice.len()]
+            }
+            Self::Bytes(_) => utf8_prefix(slice),
+        }
+    }
+
+    /// The start of `rest` to show in errors
+    pub fn got(&self, rest: &'i [u8]) -> &'i str {
+        match self {
+            Self::Text(_) => take_n(self.text(rest), 20),
+            // 20 characters are at most 80 bytes
+            Self::Bytes(_) => take_n(utf8_prefix(&rest[..rest.len().min(80)]), 20),
+        }
+    }
+
+    /// A leaf with the `len` bytes at the start of `rest`
+    pub fn leaf<'g>(&self, rest: &'i [u8], len: usize) -> NodeContent<'g, 'i> {
+        match self {
+            Self::Text(_) => NodeContent::Literal(self.text(&rest[..len])),
+            Self::Bytes(_) => NodeContent::Bytes(&rest[..len]),
+        }
+    }
+
+    /// An error node for `slice`, which is only bytes for bytes
+    pub fn error<'g>(&self, slice: &'i [u8]) -> NodeContent<'g, 'i> {
+        match self {
+            Self::Text(_) => NodeContent::Error(self.text(slice)),
+            Self::Bytes(_) => NodeContent::Bytes(slice),
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        self.positions(&[offset])[0]
+    }
+
+    /// See `utils::positions`
+    pub fn positions(&self, offsets: &[usize]) -> Vec<Position> {
+        self.positions_after((0, Position { line: 1, column: 1 }), offsets)
+    }
+
+    /// See `utils::positions_after`
+    pub fn positions_after(&self, known: (usize, Position), offsets: &[usize]) -> Vec<Position> {
+        let bytes = match self {
+            Self::Text(text) => return utils::positions_after(text, known, offsets),
+            Self::Bytes(bytes) => bytes,
+        };
+
+        let (mut cursor, mut position) = known;
+        let mut found = Vec::with_capacity(offsets.len());
+
+        for &offset in offsets {
+            for &byte in &bytes[cursor..offset] {
+                match byte {
+                    b'\n' => {
+                        position = Position {
+                            line: position.line + 1,
+                            column: 1,
+                        }
+                    }
+                    _ => position.column += 1,
+                }
+            }
+
+            cursor = offset;
+            found.push(position);
+        }
+
+        found
+    }
+
+    /// See `utils::snippet`. Bytes that aren't
+    /// UTF-8 are shown as replacement characters
+    pub fn snippet(&self, offset: usize) -> String {
+        let bytes = match self {
+            Self::Text(text) => return utils::snippet(text, offset),
+            Self::Bytes(bytes) => bytes,
+        };
+
+        let position = self.position(offset);
+        let line = bytes.split(|&byte| byte == b'\n').nth(position.line - 1);
+        let line = String::from_utf8_lossy(line.unwrap_or_default());
+
+        format!("{}\n{}^", line, " ".repeat(position.column - 1))
+    }
+}
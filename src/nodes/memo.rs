@@ -0,0 +1,161 @@
+use std::fmt;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use super::error::ParseError;
+use super::node::Node;
+use super::node::NodeContent;
+
+/// A rule at some position: rules are identified by their name,
+/// positions by the length of the input left, and like for left
+/// recursion we keep track of whether we're in an atomic rule
+pub type MemoKey<'g> = (&'g str, usize, bool);
+
+/// What a rule parsed. The node is shared by the memo table
+/// and the tree, so remembering it doesn't copy it
+pub type Parsed<'g, 'i> = Result<(Arc<Node<'g, 'i>>, usize), ParseError<'g, 'i>>;
+
+/// How a result is kept in the table, with how far the rule looked,
+/// see `ParseState::look`. Errors are boxed, since they're big and
+/// most entries are nodes
+type Entry<'g, 'i> = (
+    Result<(Arc<Node<'g, 'i>>, usize), Box<ParseError<'g, 'i>>>,
+    usize,
+);
+
+/// The results of the rules parsed until now, for packrat parsing
+#[derive(Debug, Default)]
+pub struct Memo<'g, 'i> {
+    /// turned off for a while when skipping,
+    /// since `ParseState` remembers that itself
+    pub enabled: bool,
+    table: FxHashMap<MemoKey<'g>, Entry<'g, 'i>>,
+    hits: usize,
+    misses: usize,
+}
+
+/// How much the memo table was used during a parse
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MemoStats {
+    pub entries: usize,
+    pub hits: usize,
+    pub misses: usize,
+    /// how much memory the table takes, with the nodes and
+    /// errors in it. Nodes are shared with the tree
+    pub bytes: usize,
+}
+
+impl<'g, 'i> Memo<'g, 'i> {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    /// What the rule parsed, and how far it looked
+    pub fn get(&mut self, key: &MemoKey<'g>) -> Option<(Parsed<'g, 'i>, usize)> {
+        if !self.enabled {
+            return None;
+        }
+
+        match self.table.get(key) {
+            Some((parsed, looked)) => {
+                self.hits += 1;
+                Some((parsed.clone().map_err(|err| *err), *looked))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: MemoKey<'g>, parsed: &Parsed<'g, 'i>, looked: usize) {
+        if self.enabled {
+            self.table
+                .insert(key, (parsed.clone().map_err(Box::new), looked));
+        }
+    }
+
+    /// Forgets what was parsed, but not how the table was used
+    pub fn clear(&mut self) {
+        self.table = FxHashMap::default();
+    }
+
+    /// Forgets what was parsed before where `len` of the input is
+    /// left, since nothing goes back there once it's been committed to
+    pub fn clear_before(&mut self, len: usize) {
+        self.table.retain(|(_, left, _), _| *left <= len);
+    }
+
+    pub fn stats(&self) -> MemoStats {
+        let entry = size_of::<(MemoKey<'g>, Entry<'g, 'i>)>();
+        let mut bytes = self.table.capacity() * entry;
+        // nodes can be in more than one entry
+        let mut seen = FxHashSet::default();
+
+        for (parsed, _) in self.table.values() {
+            bytes += match parsed {
+                Ok((node, _)) => node_bytes(node, &mut seen),
+                Err(err) => size_of::<ParseError>() + error_bytes(err),
+            };
+        }
+
+        MemoStats {
+            entries: self.table.len(),
+            hits: self.hits,
+            misses: self.misses,
+            bytes,
+        }
+    }
+}
+
+/// The memory taken by `node` and the nodes in it,
+/// without the ones in `seen`
+fn node_bytes<'a>(node: &'a Arc<Node>, seen: &mut FxHashSet<*const Node<'a, 'a>>) -> usize {
+    let mut bytes = 0;
+    let mut to_visit = vec![node];
+
+    while let Some(node) = to_visit.pop() {
+        if !seen.insert(Arc::as_ptr(node)) {
+            continue;
+        }
+
+        // an `Arc` keeps two counters next to the node
+        bytes += 2 * size_of::<usize>() + size_of::<Node>();
+        if let NodeContent::Cons(list) = &node.content {
+            bytes += list.capacity() * size_of::<Arc<Node>>();
+            to_visit.extend(list);
+        }
+    }
+
+    bytes
+}
+
+/// The memory an error takes, besides its own size
+fn error_bytes(err: &ParseError) -> usize {
+    match err {
+        ParseError::Expected { expected, .. } => expected.capacity(),
+        ParseError::Unexpected {
+            expected, snippet, ..
+        } => {
+            let strings: usize = expected.iter().map(String::capacity).sum();
+            expected.capacity() * size_of::<String>() + strings + snippet.capacity()
+        }
+        _ => 0,
+    }
+}
+
+impl fmt::Display for MemoStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "memo: {} entries, {} hits, {} misses, about {} bytes",
+            self.entries, self.hits, self.misses, self.bytes
+        )
+    }
+}
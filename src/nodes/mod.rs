@@ -1,3 +1,12 @@
 pub mod display;
 pub mod error;
+pub mod incremental;
+pub mod input;
+pub mod memo;
 pub mod node;
+pub mod options;
+pub mod span;
+pub mod state;
+pub mod stream;
+pub mod trace;
+pub mod visitor;
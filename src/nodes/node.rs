@@ -1,90 +1,1195 @@
 use serde::Serialize;
 
+use std::ops::ControlFlow;
+use std::ops::Range;
+use std::sync::Arc;
+
 use super::error::ParseError;
+use super::incremental::changed_ranges;
+use super::incremental::Edit;
+use super::input::Input;
+use super::memo::MemoStats;
+use super::memo::Parsed;
+use super::options::ParseOptions;
+use super::span::Span;
+use super::state::ParseState;
+use super::trace::Trace;
+use super::visitor::Visited;
+use super::visitor::Visitor;
+use crate::rules::choice::ChoiceStrategy;
 use crate::rules::grammar::Grammar;
+use crate::rules::literal::Literal;
 use crate::rules::literal::LiteralContent;
+use crate::rules::operators::Associativity;
+use crate::rules::operators::Operator;
+use crate::rules::operators::OperatorKind;
+use crate::rules::operators::OperatorTable;
 use crate::rules::rule::Rule;
 use crate::rules::rule::RuleList;
 use crate::rules::rule::RuleOrs;
 use crate::rules::rule::RulePiece;
 use crate::rules::rule::RulePieceContent;
 use crate::spec_parser::rule_piece::Repetition;
-use crate::utils::take_n;
+use crate::utils::first_char;
+use crate::utils::is_word_char;
+use crate::utils::utf8_prefix;
+use crate::utils::Position;
+
+/// How much stack parsing a rule can need before it gets to the
+/// rules inside of it, and how much more stack it gets if there's
+/// less than that left
+const STACK_RED_ZONE: usize = 128 * 1024;
+const STACK_GROWTH: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Node<'grammar, 'input> {
+    pub name: Option<&'grammar str>,
+    pub content: NodeContent<'grammar, 'input>,
+    pub span: Span,
+    /// whether a rule matched it, and it's not a repetition of the
+    /// rule or an operand of it, see `Node::reparse`
+    #[serde(skip)]
+    pub is_rule: bool,
+    /// for a rule, how many bytes it looked at from where it started,
+    /// counting the end of the input as one more, see `Node::reparse`
+    #[serde(skip)]
+    pub looked: usize,
+}
+
+/// Trees can be as deep as the input is long, so they're
+/// compared and dropped with a stack instead of recursing
+impl PartialEq for Node<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        let mut to_compare = vec![(self, other)];
+
+        while let Some((node, other)) = to_compare.pop() {
+            if node.name != other.name || node.span != other.span {
+                return false;
+            }
+
+            match (&node.content, &other.content) {
+                (NodeContent::Cons(list), NodeContent::Cons(others))
+                    if list.len() == others.len() =>
+                {
+                    to_compare.extend(
+                        list.iter()
+                            .map(Arc::as_ref)
+                            .zip(others.iter().map(Arc::as_ref)),
+                    );
+                }
+                (NodeContent::Cons(_), _) | (_, NodeContent::Cons(_)) => return false,
+                (content, other) if content != other => return false,
+                _ => {}
+            }
+        }
+
+        true
+    }
+}
+
+impl Drop for Node<'_, '_> {
+    fn drop(&mut self) {
+        let mut to_drop = match &mut self.content {
+            NodeContent::Cons(list) => std::mem::take(list),
+            _ => return,
+        };
+
+        // nodes shared with other trees aren't dropped yet
+        while let Some(node) = to_drop.pop() {
+            if let Some(mut node) = Arc::into_inner(node) {
+                if let NodeContent::Cons(list) = &mut node.content {
+                    to_drop.append(list);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum NodeContent<'grammar, 'input> {
+    Literal(&'input str),
+    /// children are shared, so copying a node doesn't copy them
+    Cons(Vec<Arc<Node<'grammar, 'input>>>),
+    /// input that couldn't be parsed, see `Node::from_grammar_recovering`
+    Error(&'input str),
+    /// what's matched in byte mode, and what couldn't be parsed
+    /// there, since it isn't text, see `Input`
+    Bytes(&'input [u8]),
+}
+
+/// A piece of input that was skipped by one of the
+/// grammar's `@skip` rules, and so isn't part of the tree
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Trivia<'input> {
+    pub offset: usize,
+    pub text: &'input str,
+}
+
+/// How far `Node::visit_grammar` has located the tree, when it
+/// locates the nodes one after the other, inside of the rules it
+/// told the visitor about and hasn't exited yet
+struct Walked {
+    /// where the text of the nodes until now ends, and its position
+    cursor: usize,
+    position: Position,
+    /// where the text of each rule that's open starts, once it has any
+    open: Vec<Option<(usize, Position)>>,
+}
+
+impl Walked {
+    fn new() -> Self {
+        Self {
+            cursor: 0,
+            position: Position { line: 1, column: 1 },
+            open: Vec::new(),
+        }
+    }
+
+    fn enter(&mut self) {
+        self.open.push(None);
+    }
+
+    /// Closes the innermost open rule, and returns its span: from where
+    /// its text starts to where the last node in it ends, or where the
+    /// text before it ends if it has none
+    fn exit(&mut self) -> Span {
+        let (start, start_position) = self
+            .open
+            .pop()
+            .flatten()
+            .unwrap_or((self.cursor, self.position));
+
+        Span {
+            start,
+            end: self.cursor,
+            start_position,
+            end_position: self.position,
+        }
+    }
+
+    /// Sets the spans of a node that comes after the ones until now
+    fn locate<'i>(&mut self, node: &mut Node<'_, 'i>, input: Input<'i>) {
+        let known = (self.cursor, self.position);
+        if node.locate_after(input, &mut self.cursor, known) {
+            let start = Some((node.span.start, node.span.start_position));
+
+            // the open rules without text yet start with this node
+            for open in self.open.iter_mut().rev().take_while(|open| open.is_none()) {
+                *open = start;
+            }
+        }
+
+        self.position = node.span.end_position;
+    }
+}
+
+impl<'g, 'i> Node<'g, 'i> {
+    /// A node without a span yet, see `locate`
+    pub fn new(name: Option<&'g str>, content: NodeContent<'g, 'i>) -> Self {
+        Self {
+            name,
+            content,
+            span: Span::default(),
+            is_rule: false,
+            looked: 0,
+        }
+    }
+
+    /// Parses the longest beginning of the input the main rule
+    /// matches, and returns how long it is. The rest of the input
+    /// is ignored, use `from_grammar_strict` to parse all of it
+    pub fn from_grammar(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        Self::from_grammar_with_options(gram, input, &ParseOptions::default())
+    }
+
+    /// Parses all of the input, failing if there's something
+    /// left after the main rule
+    pub fn from_grammar_strict(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+    ) -> Result<Self, ParseError<'g, 'i>> {
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        Self::from_grammar_with_options(gram, input, &options).map(|(node, _)| node)
+    }
+
+    pub fn from_grammar_with_options(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), options);
+        let (node, len) = Self::parse_main(&mut state, options)?;
+
+        Ok((Self::located(&mut state, node), len))
+    }
+
+    /// Parses bytes instead of text, see `Input`. The grammar should
+    /// have `@bytes`, so its byte ranges can go past 0x7F. Offsets,
+    /// in spans and errors, count bytes
+    pub fn from_bytes(
+        gram: &'g Grammar<'g>,
+        input: &'i [u8],
+        options: &ParseOptions,
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        let mut state = ParseState::with_input(gram, Input::Bytes(input), options);
+        let (node, len) = Self::parse_main(&mut state, options)?;
+
+        Ok((Self::located(&mut state, node), len))
+    }
+
+    /// Like `from_grammar_with_options`, but also returns
+    /// how much the memo table was used
+    pub fn from_grammar_with_stats(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> Result<(Self, usize, MemoStats), ParseError<'g, 'i>> {
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), options);
+        let (node, len) = Self::parse_main(&mut state, options)?;
+        let stats = state.memo.stats();
+
+        Ok((Self::located(&mut state, node), len, stats))
+    }
+
+    /// Like `from_grammar_with_options`, but also returns every
+    /// rule and alternative that was tried, even if the parse
+    /// failed. Only `rules` get traced, or all of them if it's empty
+    pub fn from_grammar_traced(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+        rules: Vec<String>,
+    ) -> (Result<(Self, usize), ParseError<'g, 'i>>, Trace<'g>) {
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), options);
+        state.trace = Some(Trace::new(rules));
+
+        let parsed = Self::parse_main(&mut state, options);
+        let parsed = parsed.map(|(node, len)| (Self::located(&mut state, node), len));
+        let mut trace = state.trace.take().unwrap_or_default();
+        trace.locate(state.input);
+
+        (parsed, trace)
+    }
+
+    /// Parses the input again after `edit`, reusing the nodes of
+    /// `old` it doesn't change. `old` must be the tree of the input
+    /// before the edit, and `input` the one after. Returns the new
+    /// tree, and where it's different from the old one
+    pub fn reparse(
+        gram: &'g Grammar<'g>,
+        old: &Node<'g, '_>,
+        edit: &Edit,
+        input: &'i str,
+    ) -> Result<(Self, Vec<Range<usize>>), ParseError<'g, 'i>> {
+        Self::reparse_with_options(gram, old, edit, input, &ParseOptions::default())
+    }
+
+    pub fn reparse_with_options(
+        gram: &'g Grammar<'g>,
+        old: &Node<'g, '_>,
+        edit: &Edit,
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> Result<(Self, Vec<Range<usize>>), ParseError<'g, 'i>> {
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), options);
+        state.reusable = old.reusable(gram, edit, input)?;
+
+        let (node, _) = Self::parse_main(&mut state, options)?;
+        let node = Self::located(&mut state, node);
+        let changed = changed_ranges(old, &node);
+
+        Ok((node, changed))
+    }
+
+    /// Parses the input like `from_grammar_with_options`, but instead
+    /// of returning a tree it tells `visitor` about its nodes, in the
+    /// order they come in. A visitor that breaks stops the parser there.
+    ///
+    /// When the main rule has a single alternative, like
+    /// `<file>: <header> <line>*;`, nothing parsed in it can be taken
+    /// back, so each of its pieces, and each item of its `*` repetitions,
+    /// is told about as soon as it's parsed, and then dropped. Only the
+    /// tree of a single piece or item is ever kept: what's inside of them
+    /// is parsed whole first, even another repetition, since an item can
+    /// still fail after it. Main rules with more alternatives get parsed
+    /// all the way first. An error fails the visit, even if what came
+    /// before it was told about already
+    pub fn visit_grammar(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+        visitor: &mut impl Visitor<'g, 'i>,
+    ) -> Result<Visited, ParseError<'g, 'i>> {
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), options);
+        let main = gram.main().unwrap();
+
+        match Self::streamed_pieces(gram, main) {
+            Some(pieces) => Self::visit_main(&mut state, main, pieces, options, visitor),
+            None => {
+                let (node, len) = Self::parse_main(&mut state, options)?;
+                let node = Self::located(&mut state, node);
+                let stopped = node.walk(visitor).is_break();
+
+                Ok(Visited { len, stopped })
+            }
+        }
+    }
+
+    /// The pieces of `rule`, if it has a single alternative, and
+    /// nothing else is done with what they match
+    fn streamed_pieces(gram: &'g Grammar<'g>, rule: &'g Rule<'g>) -> Option<&'g [RulePiece<'g>]> {
+        let plain = !gram.is_atomic(rule.name)
+            && !gram.is_left_recursive(rule.name)
+            && !gram.identifiers.contains(rule.name)
+            && !gram.operators.contains_key(rule.name);
+
+        match (rule.rule.0.as_slice(), plain) {
+            ([list], true) => Some(&list.0),
+            _ => None,
+        }
+    }
+
+    /// Does what `parse_main` does for a main rule with a single
+    /// alternative, see `visit_grammar`, but walks its pieces
+    fn visit_main(
+        state: &mut ParseState<'g, 'i>,
+        main: &Rule<'g>,
+        pieces: &'g [RulePiece<'g>],
+        options: &ParseOptions,
+        visitor: &mut impl Visitor<'g, 'i>,
+    ) -> Result<Visited, ParseError<'g, 'i>> {
+        let input = state.input;
+
+        state.step()?;
+        state.enter()?;
+        let visited = Self::visit_pieces(state, main, pieces, visitor);
+        state.depth -= 1;
+
+        let rest = match visited? {
+            ControlFlow::Continue(rest) => rest,
+            ControlFlow::Break(rest) => {
+                return Ok(Visited {
+                    len: input.len() - rest.len(),
+                    stopped: true,
+                })
+            }
+        };
+
+        // skipped text at the end of the input belongs to the main rule
+        let end = Self::skip(state, rest)?;
+        let len = input.len() - end.len();
+
+        if options.strict && !end.is_empty() {
+            return Err(Self::unconsumed(state, len));
+        }
+
+        Ok(Visited {
+            len,
+            stopped: false,
+        })
+    }
+
+    /// Parses and walks the pieces of the main rule one after the other.
+    /// Returns the input after them, or where the visitor stopped
+    fn visit_pieces(
+        state: &mut ParseState<'g, 'i>,
+        main: &Rule<'g>,
+        pieces: &'g [RulePiece<'g>],
+        visitor: &mut impl Visitor<'g, 'i>,
+    ) -> Result<ControlFlow<&'i [u8], &'i [u8]>, ParseError<'g, 'i>> {
+        let mut rest = state.input.bytes();
+        let mut walked = Walked::new();
+
+        walked.enter();
+        if visitor.enter(main.name).is_break() {
+            return Ok(ControlFlow::Break(rest));
+        }
+
+        for piece in pieces {
+            rest = Self::skip(state, rest)?;
+
+            let flow = match (&piece.content, &piece.repetition) {
+                (RulePieceContent::Rule(ruleref), Repetition::RepeatSeparate) => {
+                    let item = state.gram.get(*ruleref).expect("can't find rule");
+                    Self::visit_items(state, main, item, &mut rest, &mut walked, visitor)?
+                }
+                _ => {
+                    let (node, len) = match Self::from_rule_piece(state, piece, rest) {
+                        Ok(parsed) => parsed,
+                        Err(err) => return Err(Self::failed_main(state, main, err)),
+                    };
+                    rest = &rest[len..];
+                    Self::walk_parsed(state, node, rest, &mut walked, visitor)
+                }
+            };
+
+            if flow.is_break() {
+                return Ok(ControlFlow::Break(rest));
+            }
+        }
+
+        match visitor.exit(main.name, &walked.exit()) {
+            ControlFlow::Continue(()) => Ok(ControlFlow::Continue(rest)),
+            ControlFlow::Break(()) => Ok(ControlFlow::Break(rest)),
+        }
+    }
+
+    /// Parses and walks the items of a `*` repetition of the main
+    /// rule one at a time, moving `rest` after each of them
+    fn visit_items(
+        state: &mut ParseState<'g, 'i>,
+        main: &Rule<'g>,
+        item: &'g Rule<'g>,
+        rest: &mut &'i [u8],
+        walked: &mut Walked,
+        visitor: &mut impl Visitor<'g, 'i>,
+    ) -> Result<ControlFlow<()>, ParseError<'g, 'i>> {
+        walked.enter();
+        if visitor.enter(item.name).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+
+        let mut found = false;
+
+        'item_loop: loop {
+            let start = Self::skip(state, rest)?;
+            let (node, len) = match Self::parse_rule(state, item, start) {
+                Ok(parsed) => parsed,
+                Err(err) if found && !err.is_fatal() => break 'item_loop,
+                Err(err) => return Err(Self::failed_main(state, main, err)),
+            };
+            *rest = &start[len..];
+            found = true;
+
+            if Self::walk_parsed(state, node, rest, walked, visitor).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+
+            // matching nothing again would go on forever
+            if len == 0 {
+                break 'item_loop;
+            }
+        }
+
+        Ok(visitor.exit(item.name, &walked.exit()))
+    }
+
+    /// Locates a node that was just parsed, and walks it. Nothing goes
+    /// back before `rest` after it, so what the memo table has from
+    /// before then can be forgotten, and the node isn't shared anymore
+    fn walk_parsed(
+        state: &mut ParseState<'g, 'i>,
+        node: Arc<Self>,
+        rest: &'i [u8],
+        walked: &mut Walked,
+        visitor: &mut impl Visitor<'g, 'i>,
+    ) -> ControlFlow<()> {
+        state.memo.clear_before(rest.len());
+
+        let mut node = Arc::unwrap_or_clone(node);
+        walked.locate(&mut node, state.input);
+        node.walk(visitor)
+    }
+
+    /// The error for a piece of the main rule that didn't match,
+    /// like `parse_main` gives when the main rule doesn't
+    fn failed_main(
+        state: &ParseState<'g, 'i>,
+        main: &Rule<'g>,
+        err: ParseError<'g, 'i>,
+    ) -> ParseError<'g, 'i> {
+        if err.is_fatal() {
+            return err;
+        }
+
+        Self::unexpected(state).unwrap_or(ParseError::Expected {
+            parsing: main.name,
+            expected: main.name.into(),
+            got: state.input.got(state.input.bytes()),
+        })
+    }
+
+    /// Parses the main rule, and what gets skipped after it.
+    /// The tree isn't located yet, see `located`
+    fn parse_main(state: &mut ParseState<'g, 'i>, options: &ParseOptions) -> Parsed<'g, 'i> {
+        let (gram, input) = (state.gram, state.input.bytes());
+        let (node, len) = match Self::parse_rule(state, gram.main().unwrap(), input) {
+            Ok(parsed) => parsed,
+            Err(err) if err.is_fatal() => return Err(err),
+            Err(err) => return Err(Self::unexpected(state).unwrap_or(err)),
+        };
+
+        // skipped text at the end of the input belongs to the main rule
+        let rest = Self::skip(state, &input[len..])?;
+        let len = input.len() - rest.len();
+
+        if options.strict && !rest.is_empty() {
+            return Err(Self::unconsumed(state, len));
+        }
+
+        Ok((node, len))
+    }
+
+    /// The tree, with its spans set. The memo table and the reusable
+    /// nodes get emptied first, or the nodes they share would be
+    /// copied to set them
+    fn located(state: &mut ParseState<'g, 'i>, node: Arc<Self>) -> Self {
+        state.memo.clear();
+        state.reusable.clear();
+
+        let mut node = Arc::unwrap_or_clone(node);
+        node.locate(state.input);
+        node
+    }
+
+    /// Parses all of the input, going on after errors. When an item
+    /// of a repetition fails partway through, everything until the
+    /// end of the next `@sync` token becomes an error node, and the
+    /// repetition starts again after it. Returns the tree with every
+    /// error found, in input order.
+    ///
+    /// Only `*` repetitions recover: a `+` repetition is a single
+    /// token, and a rule that isn't repeated has nowhere to start
+    /// again, so errors anywhere else fail the whole item around them
+    pub fn from_grammar_recovering(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+    ) -> (Self, Vec<ParseError<'g, 'i>>) {
+        Self::from_grammar_recovering_with_options(gram, input, &ParseOptions::default())
+    }
+
+    /// Like `from_grammar_recovering`. The parse is never memoized,
+    /// and errors after the main rule don't depend on `strict`
+    pub fn from_grammar_recovering_with_options(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+    ) -> (Self, Vec<ParseError<'g, 'i>>) {
+        // a memoized failure doesn't say how far it got
+        let options = ParseOptions {
+            memoize: false,
+            ..options.clone()
+        };
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), &options);
+        state.recovering = true;
+        let input = state.input.bytes();
+
+        let main = gram.main().unwrap();
+        let (mut node, len) = match Self::parse_rule(&mut state, main, input) {
+            Ok((node, len)) => (Arc::unwrap_or_clone(node), len),
+            Err(err) => {
+                let err = match err.is_fatal() {
+                    true => err,
+                    false => Self::unexpected(&state).unwrap_or(err),
+                };
+                state.errors.push(err);
+
+                let node = Self::new(Some(main.name), NodeContent::Cons(Vec::new()));
+                (node, 0)
+            }
+        };
+
+        let rest = Self::skip(&mut state, &input[len..]).unwrap_or(&input[len..]);
+        if !rest.is_empty() {
+            if len > 0 {
+                let end = input.len() - rest.len();
+                state.errors.push(Self::unconsumed(&state, end));
+            }
+
+            if let NodeContent::Cons(list) = &mut node.content {
+                list.push(Arc::new(Self::new(None, state.input.error(rest))));
+            }
+        }
+
+        state.errors.sort_by_key(|err| match err {
+            ParseError::Unexpected { offset, .. } | ParseError::Unconsumed { offset, .. } => {
+                *offset
+            }
+            _ => input.len(),
+        });
+        node.locate(state.input);
+        (node, state.errors)
+    }
+
+    /// The error for the furthest failure
+    fn unexpected(state: &ParseState<'g, 'i>) -> Option<ParseError<'g, 'i>> {
+        let input = state.input;
+        let offset = state.furthest.offset(input)?;
+
+        Some(ParseError::Unexpected {
+            offset,
+            position: input.position(offset),
+            expected: state.furthest.expected.iter().cloned().collect(),
+            snippet: input.snippet(offset),
+        })
+    }
+
+    /// The error for input left after the main rule, which matched
+    /// until `end`. Unless something failed further than that,
+    /// then that's what's wrong with the input
+    fn unconsumed(state: &ParseState<'g, 'i>, end: usize) -> ParseError<'g, 'i> {
+        let input = state.input;
+
+        match state.furthest.offset(input) {
+            Some(furthest) if furthest > end => Self::unexpected(state).unwrap(),
+            _ => ParseError::Unconsumed {
+                parsing: state.gram.main().unwrap().name,
+                offset: end,
+                position: input.position(end),
+                got: input.got(&input.bytes()[end..]),
+            },
+        }
+    }
+
+    /// Like `from_grammar`, but also returns everything that
+    /// was skipped while parsing, in input order
+    pub fn from_grammar_with_trivia(
+        gram: &'g Grammar<'g>,
+        input: &'i str,
+    ) -> Result<(Self, usize, Vec<Trivia<'i>>), ParseError<'g, 'i>> {
+        let (node, len) = Self::from_grammar(gram, input)?;
+        let trivia = node.trivia(input, len);
+
+        Ok((node, len, trivia))
+    }
+
+    pub fn from_rule(
+        gram: &'g Grammar<'g>,
+        rule: &Rule<'g>,
+        input: &'i str,
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        let input = Input::new(gram, input);
+        let mut state = ParseState::with_input(gram, input, &ParseOptions::default());
+        let (node, len) = Self::parse_rule(&mut state, rule, input.bytes())?;
+        Ok((Self::located(&mut state, node), len))
+    }
+
+    /// Parses `rule` at the start of `input`. If `more` is true,
+    /// `input` is only the beginning of the text, and the result is
+    /// `None` when the text after it could change what the rule
+    /// matches, that is when the parser got to the end of `input`
+    pub fn from_rule_prefix(
+        gram: &'g Grammar<'g>,
+        rule: &Rule<'g>,
+        input: &'i str,
+        options: &ParseOptions,
+        more: bool,
+    ) -> Result<Option<(Self, usize)>, ParseError<'g, 'i>> {
+        let mut state = ParseState::with_input(gram, Input::new(gram, input), options);
+        state.more = more;
+        let parsed = Self::parse_rule(&mut state, rule, input.as_bytes());
+
+        match parsed {
+            Err(err) if err.is_fatal() => Err(err),
+            _ if state.reached_end => Ok(None),
+            Ok((node, len)) => Ok(Some((Self::located(&mut state, node), len))),
+            Err(err) => Err(Self::unexpected(&state).unwrap_or(err)),
+        }
+    }
+
+    fn parse_rule(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        input: &'i [u8],
+    ) -> Parsed<'g, 'i> {
+        let attempt = state.trace_start(rule.name, None, input);
+        let parsed = Self::parse_rule_untraced(state, rule, input);
+        state.trace_finish(attempt, &parsed);
+
+        parsed
+    }
+
+    /// The node is shared with the memo table
+    fn parse_rule_untraced(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        input: &'i [u8],
+    ) -> Parsed<'g, 'i> {
+        state.step()?;
+
+        let key = (rule.name, input.len(), state.atomic_depth > 0);
+        if let Some((parsed, looked)) = state.memo.get(&key) {
+            state.looked = state.looked.min(looked);
+            return parsed;
+        }
+        if let Some(reused) = Self::reuse(state, rule, input)? {
+            return Ok(reused);
+        }
+
+        state.enter()?;
+        // rules nest as deep as the input does, so the stack
+        // gets more room when it's about to run out
+        // how far this rule looks, and not the ones around it
+        let outer = std::mem::replace(&mut state.looked, usize::MAX);
+        let parsed = stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || {
+            match state.gram.is_left_recursive(rule.name) {
+                true => Self::grow_rule(state, rule, input),
+                false => Self::parse_rule_once(state, rule, input),
+            }
+        });
+        let looked = state.looked;
+        state.looked = outer.min(looked);
+
+        let parsed = parsed.map(|(mut node, len)| {
+            node.looked = (input.len() + 1).saturating_sub(looked);
+            (Arc::new(node), len)
+        });
+        state.depth -= 1;
+
+        if !state.depends_on_growing(input.len()) {
+            state.memo.insert(key, &parsed, looked);
+        }
+
+        parsed
+    }
+
+    /// What `rule` parsed here before an edit, see `reparse`. Nodes
+    /// start after what gets skipped, and outside of atomic rules
+    /// that doesn't change what the rule parses
+    fn reuse(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        input: &'i [u8],
+    ) -> Result<Option<(Arc<Self>, usize)>, ParseError<'g, 'i>> {
+        if state.reusable.is_empty() {
+            return Ok(None);
+        }
+
+        let start = match state.atomic_depth {
+            0 => Self::skip(state, input)?,
+            _ => input,
+        };
+        let key = (rule.name, start.len(), state.atomic_depth > 0);
+        let skipped = input.len() - start.len();
+
+        let reused = state.reused(&key);
+        if let Some((node, _)) = &reused {
+            state.look(start, node.looked);
+        }
+
+        Ok(reused.map(|(node, len)| (node, skipped + len)))
+    }
+
+    /// Parses a left recursive rule by growing a seed: the first time
+    /// around, the recursive call fails, so only the alternatives that
+    /// don't recurse can match. Then we parse the rule again and again,
+    /// with the recursive call returning the previous match, until
+    /// the match stops getting longer
+    fn grow_rule(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        input: &'i [u8],
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        let key = (rule.name, input.len(), state.atomic_depth > 0);
+
+        // we're inside of the rule, and recursing
+        if let Some(seed) = state.growing.get(&key).cloned() {
+            if let Some((node, _)) = &seed {
+                state.look(input, node.looked);
+            }
+
+            return seed.ok_or(ParseError::Expected {
+                parsing: rule.name,
+                expected: rule.name.into(),
+                got: state.input.got(input),
+            });
+        }
+
+        state.growing.insert(key, None);
+        let mut grown: Option<(Self, usize)> = None;
+
+        let parsed = 'grow_loop: loop {
+            let (mut node, len) = match Self::parse_rule_once(state, rule, input) {
+                Ok(parsed) => parsed,
+                Err(err) => match grown {
+                    Some(grown) if !err.is_fatal() => break 'grow_loop Ok(grown),
+                    _ => break 'grow_loop Err(err),
+                },
+            };
+
+            // the seed looked as far as the rule did until now
+            node.looked = (input.len() + 1).saturating_sub(state.looked);
+
+            match grown {
+                Some((_, grown_len)) if len <= grown_len => break 'grow_loop Ok(grown.unwrap()),
+                _ => {
+                    state.growing.insert(key, Some((node.clone(), len)));
+                    grown = Some((node, len));
+                }
+            }
+        };
+
+        state.growing.remove(&key);
+        parsed
+    }
+
+    fn parse_rule_once(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        input: &'i [u8],
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        let atomic = state.gram.is_atomic(rule.name);
+        let token = state.token;
+        if atomic {
+            if state.atomic_depth == 0 {
+                state.token = Some((rule.name, input.len()));
+            }
+            state.atomic_depth += 1;
+        }
+
+        let parsed = match state.gram.operators.get(rule.name) {
+            Some(table) => Self::from_operators(state, rule, table, 0, input),
+            None => Self::from_rule_ors(state, &rule.rule, rule.name, input),
+        };
+
+        if atomic {
+            state.atomic_depth -= 1;
+            state.token = token;
+        }
+
+        let (mut node, len) = parsed?;
+        node.name = Some(rule.name);
+        node.is_rule = true;
+
+        if state.gram.identifiers.contains(rule.name) {
+            match std::str::from_utf8(&input[..len]) {
+                Ok(word) if state.gram.is_keyword(word) => {
+                    return Err(ParseError::Reserved {
+                        parsing: rule.name,
+                        keyword: word,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        Ok((node, len))
+    }
+
+    /// Precedence climbing over a rule declared with `@operators`:
+    /// parses an operand, optionally preceded by a prefix operator, and
+    /// then keeps combining it with the operators that follow, as long
+    /// as they bind at least as tight as `min_precedence`
+    fn from_operators(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        table: &'g OperatorTable<'g>,
+        min_precedence: usize,
+        input: &'i [u8],
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        // operands nest without going through `parse_rule`
+        state.enter()?;
+        let parsed = Self::climb_operators(state, rule, table, min_precedence, input);
+        state.depth -= 1;
+
+        parsed
+    }
+
+    fn climb_operators(
+        state: &mut ParseState<'g, 'i>,
+        rule: &Rule<'g>,
+        table: &'g OperatorTable<'g>,
+        min_precedence: usize,
+        input: &'i [u8],
+    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        let mut rest = Self::skip(state, input)?;
+
+        let prefixed = match Self::longest_operator(state, table.prefix(), rest)? {
+            Some((op, op_node, len)) => {
+                Self::from_operators(state, rule, table, op.precedence, &rest[len..]).map(
+                    |(operand, operand_len)| (vec![op_node, Arc::new(operand)], len + operand_len),
+                )
+            }
+            None => Err(ParseError::Expected {
+                parsing: rule.name,
+                expected: "prefix operator".into(),
+                got: state.input.got(rest),
+            }),
+        };
+
+        let mut lhs = match prefixed {
+            Ok((nodes, len)) => {
+                rest = &rest[len..];
+                Self::new(Some(rule.name), NodeContent::Cons(nodes))
+            }
+            Err(err) if err.is_fatal() => return Err(err),
+            Err(_) => {
+                let (mut operand, len) = Self::from_rule_ors(state, &rule.rule, rule.name, rest)?;
+                operand.name = Some(rule.name);
+                rest = &rest[len..];
+                operand
+            }
+        };
+
+        'operator_loop: loop {
+            let after_skip = Self::skip(state, rest)?;
+            let operators = table.infix().filter(|op| op.precedence >= min_precedence);
+
+            let (op, op_node, len) = match Self::longest_operator(state, operators, after_skip)? {
+                Some(found) => found,
+                None => break 'operator_loop,
+            };
+            let after_op = &after_skip[len..];
+
+            let nodes = match op.kind {
+                OperatorKind::Postfix => {
+                    rest = after_op;
+                    vec![Arc::new(lhs), op_node]
+                }
+                OperatorKind::Binary(associativity) => {
+                    let next_precedence = match associativity {
+                        Associativity::Left => op.precedence + 1,
+                        Associativity::Right => op.precedence,
+                    };
+
+                    let (rhs, rhs_len) =
+                        match Self::from_operators(state, rule, table, next_precedence, after_op) {
+                            Ok(parsed) => parsed,
+                            Err(err) if err.is_fatal() => return Err(err),
+                            Err(_) => break 'operator_loop,
+                        };
+
+                    rest = &after_op[rhs_len..];
+                    vec![Arc::new(lhs), op_node, Arc::new(rhs)]
+                }
+                OperatorKind::Prefix => unreachable!(),
+            };
+
+            lhs = Self::new(Some(rule.name), NodeContent::Cons(nodes));
+        }
+
+        Ok((lhs, input.len() - rest.len()))
+    }
 
-#[derive(Debug, PartialEq, Serialize)]
-pub struct Node<'grammar, 'input> {
-    pub name: Option<&'grammar str>,
-    pub content: NodeContent<'grammar, 'input>,
-}
+    /// Tries every operator, and returns the one that matches the
+    /// longest part of the input
+    #[allow(clippy::type_complexity)]
+    fn longest_operator(
+        state: &mut ParseState<'g, 'i>,
+        operators: impl Iterator<Item = &'g Operator<'g>>,
+        input: &'i [u8],
+    ) -> Result<Option<(&'g Operator<'g>, Arc<Self>, usize)>, ParseError<'g, 'i>> {
+        let mut longest: Option<(&'g Operator<'g>, Arc<Self>, usize)> = None;
 
-#[derive(Debug, PartialEq, Serialize)]
-pub enum NodeContent<'grammar, 'input> {
-    Literal(&'input str),
-    Cons(Vec<Node<'grammar, 'input>>),
-}
+        for op in operators {
+            match Self::from_rule_piece_content(state, &op.content, input) {
+                Ok((node, len)) => match longest {
+                    Some((_, _, longest_len)) if longest_len >= len => {}
+                    _ => longest = Some((op, node, len)),
+                },
+                Err(err) if err.is_fatal() => return Err(err),
+                Err(_) => {}
+            }
+        }
 
-impl<'g, 'i> Node<'g, 'i> {
-    pub fn from_grammar(
-        gram: &'g Grammar<'g>,
-        input: &'i str,
-    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
-        Self::from_rule(gram, gram.main().unwrap(), input)
+        Ok(longest)
     }
 
-    pub fn from_rule(
-        gram: &'g Grammar<'g>,
-        rule: &Rule<'g>,
-        input: &'i str,
-    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
-        let (mut node, len) = Self::from_rule_ors(gram, &rule.rule, rule.name, input)?;
-        node.name = Some(rule.name);
+    /// Consumes as much input as possible using the skip rules,
+    /// unless we're inside of an atomic rule. A skip rule that
+    /// fails after matching something, like a comment that never
+    /// ends, counts as a failure to report
+    fn skip(
+        state: &mut ParseState<'g, 'i>,
+        input: &'i [u8],
+    ) -> Result<&'i [u8], ParseError<'g, 'i>> {
+        if state.atomic_depth > 0 || state.gram.skip.is_empty() {
+            return Ok(input);
+        }
 
-        Ok((node, len))
+        // pieces often start where the one before them ended
+        // skipping, so this gets asked again and again
+        match state.skipped {
+            Some((from, to, looked)) if from == input.len() => {
+                state.looked = state.looked.min(looked);
+                return Ok(&input[from - to..]);
+            }
+            _ => {}
+        }
+
+        let gram = state.gram;
+        let mut rest = input;
+        let mut failed = false;
+
+        let outer = std::mem::replace(&mut state.looked, usize::MAX);
+        state.atomic_depth += 1;
+        let skipped = 'skip_loop: loop {
+            for ruleref in &gram.skip {
+                let rule = gram.get(*ruleref).expect("can't find skip rule");
+
+                // only the failures of this skip rule tell how far it got
+                let outer = std::mem::take(&mut state.furthest);
+                let enabled = std::mem::replace(&mut state.memo.enabled, false);
+                let parsed = Self::parse_rule(state, rule, rest);
+                state.memo.enabled = enabled;
+                let inner = std::mem::replace(&mut state.furthest, outer);
+
+                match parsed {
+                    // a skip rule that matches nothing would loop forever
+                    Ok((_, len)) if len > 0 => {
+                        rest = &rest[len..];
+                        continue 'skip_loop;
+                    }
+                    Err(err) if err.is_fatal() => break 'skip_loop Err(err),
+                    Err(_) if inner.rest.is_some_and(|failed| failed.len() < rest.len()) => {
+                        state.furthest.merge(inner);
+                        failed = true;
+                    }
+                    _ => {}
+                }
+            }
+
+            break 'skip_loop Ok(rest);
+        };
+        state.atomic_depth -= 1;
+        let looked = state.looked;
+        state.looked = outer.min(looked);
+
+        // failures have to be recorded again, wherever they're collected
+        if let (Ok(rest), false) = (&skipped, failed) {
+            state.skipped = Some((input.len(), rest.len(), looked));
+        }
+
+        skipped
+    }
+
+    /// How much input the skip rules consume at the start of `input`,
+    /// for parsers that don't go through `from_grammar`. If a skip
+    /// rule fails with a fatal error, nothing gets skipped
+    pub fn skipped_len(state: &mut ParseState<'g, 'i>, input: &'i str) -> usize {
+        let input = input.as_bytes();
+        match Self::skip(state, input) {
+            Ok(rest) => input.len() - rest.len(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns the text between the leaves of the tree, that is
+    /// what was skipped to build it. `len` is how much input the
+    /// tree was built from
+    pub fn trivia(&self, input: &'i str, len: usize) -> Vec<Trivia<'i>> {
+        let mut leaves = Vec::new();
+        self.leaves(&mut leaves);
+
+        let mut trivia = Vec::new();
+        let mut position = 0;
+
+        // leaves of bytes can end inside of a character, and
+        // then the text skipped after them starts after it
+        let bytes = input.as_bytes();
+        for leaf in leaves {
+            let start = leaf.as_ptr() as usize - input.as_ptr() as usize;
+            if start > position {
+                trivia.push(Trivia {
+                    offset: position,
+                    text: utf8_prefix(&bytes[position..start]),
+                });
+            }
+
+            position = position.max(start + leaf.len());
+        }
+
+        if len > position {
+            trivia.push(Trivia {
+                offset: position,
+                text: utf8_prefix(&bytes[position..len]),
+            });
+        }
+
+        trivia
+    }
+
+    fn leaves(&self, leaves: &mut Vec<&'i [u8]>) {
+        let mut to_visit = vec![self];
+
+        while let Some(node) = to_visit.pop() {
+            match &node.content {
+                NodeContent::Literal(string) | NodeContent::Error(string) => {
+                    leaves.push(string.as_bytes())
+                }
+                NodeContent::Bytes(bytes) => leaves.push(bytes),
+                NodeContent::Cons(list) => to_visit.extend(list.iter().rev().map(Arc::as_ref)),
+            }
+        }
     }
 
     fn from_rule_ors(
-        gram: &'g Grammar<'g>,
+        state: &mut ParseState<'g, 'i>,
         ors: &RuleOrs<'g>,
         name: &'g str,
-        input: &'i str,
+        input: &'i [u8],
     ) -> Result<(Self, usize), ParseError<'g, 'i>> {
-        let mut longest: Option<(Self, usize)> = None;
+        let strategy = state.gram.choice(name);
 
-        'or_loop: for or in &ors.0 {
-            let (node, len) = match Self::from_rule_list(gram, or, input) {
+        // the longest match, with the index of its alternative, and
+        // the first alternative after it that matched as much input
+        let mut longest: Option<(Self, usize, usize)> = None;
+        let mut tie = None;
+
+        'or_loop: for (i, or) in ors.0.iter().enumerate() {
+            let attempt = state.trace_start(name, Some(i + 1), input);
+            let parsed = Self::from_rule_list(state, or, input);
+            state.trace_finish(attempt, &parsed);
+
+            let (node, len) = match parsed {
                 Ok(parsed) => parsed,
-                Err(error) => match &ors.0.len() {
-                    0 => return Err(error),
-                    _ => continue 'or_loop,
-                },
+                Err(error) if error.is_fatal() => return Err(error),
+                Err(_) => continue 'or_loop,
             };
 
-            if let Some((_, longest_until_now)) = longest {
-                if len > longest_until_now {
-                    longest = Some((node, len));
+            if strategy == ChoiceStrategy::First {
+                return Ok((node, len));
+            }
+
+            match &longest {
+                Some((_, longest_len, _)) if len < *longest_len => {}
+                Some((_, longest_len, _)) if len == *longest_len => {
+                    tie = tie.or(Some(i));
+                }
+                _ => {
+                    longest = Some((node, len, i));
+                    tie = None;
                 }
-            } else {
-                longest = Some((node, len));
             }
         }
 
-        longest.ok_or(ParseError::Expected {
-            parsing: name,
-            expected: name.into(),
-            got: take_n(input, 20),
-        })
+        match (longest, tie) {
+            (Some((_, len, first)), Some(second)) if strategy == ChoiceStrategy::Unambiguous => {
+                Err(ParseError::Ambiguous {
+                    parsing: name,
+                    first: first + 1,
+                    second: second + 1,
+                    text: state.input.text(&input[..len]),
+                })
+            }
+            (Some((node, len, _)), _) => Ok((node, len)),
+            (None, _) => Err(ParseError::Expected {
+                parsing: name,
+                expected: name.into(),
+                got: state.input.got(input),
+            }),
+        }
     }
 
     fn from_rule_list(
-        gram: &'g Grammar<'g>,
+        state: &mut ParseState<'g, 'i>,
         list: &RuleList<'g>,
-        input: &'i str,
+        input: &'i [u8],
     ) -> Result<(Self, usize), ParseError<'g, 'i>> {
         let mut rest = input;
         let mut nodes = Vec::with_capacity(list.0.len());
 
         for node in &list.0 {
-            let (parsed, len) = Self::from_rule_piece(gram, &node, rest)?;
+            rest = Self::skip(state, rest)?;
+
+            let (parsed, len) = Self::from_rule_piece(state, node, rest)?;
             rest = &rest[len..];
 
             nodes.push(parsed);
@@ -92,25 +1197,20 @@ impl<'g, 'i> Node<'g, 'i> {
 
         let diff = input.len() - rest.len();
 
-        Ok((
-            Self {
-                name: None,
-                content: NodeContent::Cons(nodes),
-            },
-            diff,
-        ))
+        Ok((Self::new(None, NodeContent::Cons(nodes)), diff))
     }
 
     fn from_rule_piece(
-        gram: &'g Grammar<'g>,
+        state: &mut ParseState<'g, 'i>,
         piece: &RulePiece<'g>,
-        input: &'i str,
-    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        input: &'i [u8],
+    ) -> Parsed<'g, 'i> {
+        let gram = state.gram;
         let name = Self::get_name(gram, &piece.content);
 
         match (&piece.content, &piece.repetition) {
             (RulePieceContent::Literal(_) | RulePieceContent::Rule(_), Repetition::Single) => {
-                Self::from_rule_piece_content(gram, &piece.content, input)
+                Self::from_rule_piece_content(state, &piece.content, input)
             }
             (
                 RulePieceContent::Literal(lit),
@@ -118,19 +1218,23 @@ impl<'g, 'i> Node<'g, 'i> {
             ) => {
                 // todo: RepeatSeparate non è implementato per Literal
                 let name = Self::get_name(gram, &piece.content);
-                let matched = lit.match_str(input, true).ok_or(ParseError::Expected {
-                    parsing: name,
-                    expected: name.into(),
-                    got: take_n(input, 20),
-                })?;
-
-                Ok((
-                    Self {
-                        name: Some(name),
-                        content: NodeContent::Literal(matched),
-                    },
-                    matched.len(),
-                ))
+                state.peek(lit, input, true);
+                let matched = lit.match_bytes(input, true);
+                state.look(input, lit.looks_at(matched, true));
+                let len = match matched {
+                    Some(len) => len,
+                    None => {
+                        state.expected(input, Self::describe(lit));
+                        return Err(ParseError::Expected {
+                            parsing: name,
+                            expected: name.into(),
+                            got: state.input.got(input),
+                        });
+                    }
+                };
+
+                let node = Self::new(Some(name), state.input.leaf(input, len));
+                Ok((Arc::new(node), len))
             }
             (RulePieceContent::Rule(ruleref), repetition) => {
                 // todo: creare errore apposta
@@ -139,12 +1243,46 @@ impl<'g, 'i> Node<'g, 'i> {
 
                 let mut rest = input;
 
+                let recovering = state.recovering && *repetition == Repetition::RepeatSeparate;
+
                 'parse_loop: loop {
-                    match Self::from_rule(gram, rule, rest) {
+                    // separate items can have skipped text between them
+                    let start = match repetition {
+                        Repetition::RepeatSeparate => Self::skip(state, rest)?,
+                        _ => rest,
+                    };
+
+                    // only the failures of this item tell how far it got
+                    let outer = match recovering {
+                        true => Some(std::mem::take(&mut state.furthest)),
+                        false => None,
+                    };
+
+                    let parsed = match Self::parse_rule(state, rule, start) {
+                        Err(err) if recovering && !err.is_fatal() => {
+                            match Self::recover(state, start)? {
+                                Some((node, len)) => Ok((Arc::new(node), len)),
+                                None => Err(err),
+                            }
+                        }
+                        parsed => parsed,
+                    };
+
+                    if let Some(outer) = outer {
+                        state.furthest.merge(outer);
+                    }
+
+                    match parsed {
                         Ok((node, len)) => {
                             found.push(node);
-                            rest = &rest[len..];
+                            rest = &start[len..];
+
+                            // matching nothing again would go on forever
+                            if len == 0 {
+                                break 'parse_loop;
+                            }
                         }
+                        Err(err) if err.is_fatal() => return Err(err),
                         Err(err) => match found.len() {
                             0 => return Err(err),
                             _ => break 'parse_loop,
@@ -156,61 +1294,154 @@ impl<'g, 'i> Node<'g, 'i> {
 
                 match repetition {
                     Repetition::Single => unreachable!(),
-                    Repetition::RepeatTogether => Ok((
-                        Self {
-                            name: name.into(),
-                            content: NodeContent::Literal(&input[..len]),
-                        },
-                        len,
-                    )),
-                    Repetition::RepeatSeparate => Ok((
-                        Self {
-                            name: name.into(),
-                            content: NodeContent::Cons(found),
-                        },
-                        len,
-                    )),
+                    Repetition::RepeatTogether => {
+                        let node = Self::new(name.into(), state.input.leaf(input, len));
+                        Ok((Arc::new(node), len))
+                    }
+                    Repetition::RepeatSeparate => {
+                        let node = Self::new(name.into(), NodeContent::Cons(found));
+                        Ok((Arc::new(node), len))
+                    }
+                }
+            }
+        }
+    }
+
+    /// After an item that failed partway through `input`, records
+    /// the error and turns everything until the end of the next sync
+    /// token into an error node. Returns `None` if the item failed
+    /// right away, or if there's no sync token after the failure
+    fn recover(
+        state: &mut ParseState<'g, 'i>,
+        input: &'i [u8],
+    ) -> Result<Option<(Self, usize)>, ParseError<'g, 'i>> {
+        let start = Self::skip(state, input)?;
+        let failed = match state.furthest.rest {
+            Some(rest) if rest.len() < start.len() => rest,
+            _ => return Ok(None),
+        };
+
+        state.errors.extend(Self::unexpected(state));
+        let outer = std::mem::take(&mut state.furthest);
+        let after = Self::after_sync(state, failed);
+        state.furthest = outer;
+
+        let end = match after? {
+            Some(end) => end,
+            None => {
+                state.errors.pop();
+                return Ok(None);
+            }
+        };
+
+        let error = state.input.error(&start[..start.len() - end.len()]);
+        let node = Self::new(None, error);
+        Ok(Some((node, input.len() - end.len())))
+    }
+
+    /// The input after the first sync token in it. The input is gone
+    /// through a token at a time, skipping what the skip rules match
+    /// between them, so a sync token that's part of a longer token,
+    /// like a `;` in a string, doesn't count
+    fn after_sync(
+        state: &mut ParseState<'g, 'i>,
+        input: &'i [u8],
+    ) -> Result<Option<&'i [u8]>, ParseError<'g, 'i>> {
+        let gram = state.gram;
+        let mut rest = input;
+
+        loop {
+            rest = Self::skip(state, rest)?;
+            for token in &gram.sync {
+                if let Some(len) = token.match_bytes(rest, false) {
+                    return Ok(Some(&rest[len..]));
+                }
+            }
+
+            // the longest atomic rule is the next token,
+            // or the next character if none of them match
+            let mut len = 0;
+            for rule in gram.rules.values().filter(|rule| gram.is_atomic(rule.name)) {
+                match Self::parse_rule(state, rule, rest) {
+                    Ok((_, matched)) => len = len.max(matched),
+                    Err(err) if err.is_fatal() => return Err(err),
+                    Err(_) => {}
+                }
+            }
+            if len == 0 {
+                match first_char(rest) {
+                    Some(next) => len = next.len_utf8(),
+                    None if rest.is_empty() => return Ok(None),
+                    // a byte that isn't part of a character
+                    None => len = 1,
                 }
             }
+
+            rest = &rest[len..];
         }
     }
 
     fn from_rule_piece_content(
-        gram: &'g Grammar<'g>,
+        state: &mut ParseState<'g, 'i>,
         piece: &RulePieceContent<'g>,
-        input: &'i str,
-    ) -> Result<(Self, usize), ParseError<'g, 'i>> {
+        input: &'i [u8],
+    ) -> Parsed<'g, 'i> {
+        let gram = state.gram;
         let name = Self::get_name(gram, &piece);
 
         match piece {
             RulePieceContent::Literal(matcher) => {
-                let beginning = matcher
-                    .match_str(input, false)
-                    .ok_or(ParseError::Expected {
-                        parsing: "terminal",
-                        expected: matcher.to_string(),
-                        got: take_n(input, 20),
-                    })?;
-
-                let len = beginning.len();
-                let content = NodeContent::Literal(beginning);
-
-                Ok((
-                    Self {
-                        name: Some(name),
-                        content: content,
-                    },
-                    len,
-                ))
+                state.peek(matcher, input, false);
+                let matched = matcher.match_bytes(input, false);
+                state.look(input, matcher.looks_at(matched, false));
+                let len = match matched {
+                    Some(len) => len,
+                    None => {
+                        state.expected(input, Self::describe(matcher));
+                        return Err(ParseError::Expected {
+                            parsing: "terminal",
+                            expected: matcher.to_string(),
+                            got: state.input.got(input),
+                        });
+                    }
+                };
+
+                // a keyword can't be the beginning of a longer word
+                match matcher.content {
+                    LiteralContent::Str(word) if gram.is_keyword(word) => {
+                        state.look(&input[len..], 1);
+                        if first_char(&input[len..]).is_some_and(is_word_char) {
+                            state.expected(input, Self::describe(matcher));
+                            return Err(ParseError::Expected {
+                                parsing: "keyword",
+                                expected: matcher.to_string(),
+                                got: state.input.got(input),
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+
+                let content = state.input.leaf(input, len);
+
+                Ok((Arc::new(Self::new(Some(name), content)), len))
             }
             RulePieceContent::Rule(ruleref) => {
                 let rule = gram.get(*ruleref).unwrap();
-                let node = Self::from_rule(gram, rule, input)?;
+                let node = Self::parse_rule(state, rule, input)?;
                 Ok(node)
             }
         }
     }
 
+    /// How a literal is shown in errors, like `'if'` or `[a-z]`
+    fn describe(literal: &Literal) -> String {
+        match literal.content {
+            LiteralContent::Str(string) => format!("'{}'", string.replace('\n', "\\n")),
+            LiteralContent::Range { .. } => literal.to_string(),
+        }
+    }
+
     fn get_name(gram: &'g Grammar<'g>, content: &RulePieceContent<'g>) -> &'g str {
         match content {
             RulePieceContent::Literal(literal) => match literal.content {
@@ -225,7 +1456,15 @@ impl<'g, 'i> Node<'g, 'i> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::earley::forest::Forest;
+    use crate::nodes::error::Limit;
+    use crate::rules::error::GrammarError;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node as _;
     use rustc_hash::FxHashMap;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::Instant;
 
     #[test]
     fn ors() {
@@ -256,19 +1495,481 @@ mod tests {
         let mut rules_map = FxHashMap::default();
         rules_map.insert(0, rule);
 
-        let grammar = Grammar { rules: rules_map };
+        let grammar = Grammar {
+            rules: rules_map,
+            ..Default::default()
+        };
+
+        let (node, _) = Node::from_grammar(&grammar, input).unwrap();
+
+        let mut expected = Node::new(
+            Some("animale"),
+            NodeContent::Cons(vec![Arc::new(Node::new(
+                Some("cane"),
+                NodeContent::Literal(input),
+            ))]),
+        );
+        expected.locate(input);
+
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn skip() {
+        let spec = r#"
+@skip <spazio>;
+@atomic <parola>;
+<lista>: '[' <parola> <resto>* ']';
+<resto>: ',' <parola>;
+<parola>: <lettera> <lettera>*;
+<lettera>: [a-z];
+<spazio>: ' '+;"#
+            .trim();
+
+        let input = "[ ciao , mondo ]  ";
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        let (node, len, trivia) = Node::from_grammar_with_trivia(&grammar, input).unwrap();
+
+        assert_eq!(len, input.len());
+        assert_eq!(node.to_string().matches("\" \"").count(), 0);
+
+        let skipped: Vec<_> = trivia.iter().map(|t| (t.offset, t.text)).collect();
+        assert_eq!(
+            skipped,
+            vec![(1, " "), (6, " "), (8, " "), (14, " "), (16, "  ")]
+        );
+
+        // skipped between the items of a repetition, even atomic ones
+        let spec = r#"
+@skip <spazio> <commento>;
+@atomic <parola>;
+<frase>: <parola>* '.';
+<parola>: [a-z]+;
+<spazio>: ' '+;
+<commento>: '(' [a-z]* ')';"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = "ciao (saluto) mondo .";
+        let node = Node::from_grammar_strict(&grammar, input).unwrap();
+        assert_eq!(node.to_string().matches("\"parola\"").count(), 3);
+
+        // a comment that never ends is the error, and not what comes after it
+        let err = Node::from_grammar_strict(&grammar, "ciao (saluto mondo.").unwrap_err();
+        assert!(err.to_string().contains("')'"), "{}", err);
+    }
+
+    // writes nodes with more than one child in parentheses,
+    // to see how operations got nested
+    fn nesting(node: &Node) -> String {
+        match &node.content {
+            NodeContent::Literal(string) => string.to_string(),
+            NodeContent::Error(string) => format!("!{}!", string),
+            NodeContent::Bytes(bytes) => format!("{:?}", bytes),
+            NodeContent::Cons(list) if list.len() == 1 => nesting(&list[0]),
+            NodeContent::Cons(list) => {
+                let inner: Vec<_> = list.iter().map(|node| nesting(node)).collect();
+                format!("({})", inner.join(" "))
+            }
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let spec = "
+<lista>: '[' <parola> <resto>* ']';
+<resto>: ',' <parola>;
+<parola>: [a-z]+;
+<spazio>: [\t- ]+;
+@skip <spazio>;"
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
 
+        let input = "[ciao,\n  mondo ]";
         let (node, _) = Node::from_grammar(&grammar, input).unwrap();
+        assert_eq!((node.span.start, node.span.end), (0, input.len()));
 
-        let expected = Node {
-            name: Some("animale"),
-            content: NodeContent::Cons(vec![Node {
-                name: Some("cane"),
-                content: NodeContent::Literal("cane"),
-            }]),
+        let resto = match &node.content {
+            NodeContent::Cons(list) => &list[2],
+            _ => panic!("expected a list"),
         };
+        let span = resto.span;
+        assert_eq!(&input[span.start..span.end], ",\n  mondo");
+        assert_eq!(span.start_position, Position { line: 1, column: 6 });
+        assert_eq!(span.end_position, Position { line: 2, column: 8 });
+    }
 
-        assert_eq!(node, expected);
+    #[test]
+    fn left_recursion() {
+        let spec = r#"
+<somma>: <somma> '+' <fattore> | <somma> '-' <fattore> | <fattore>;
+<fattore>: <fattore> '*' <atomo> | <atomo>;
+<atomo>: [0-9] | '(' <somma> ')';"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let cases = [
+            ("1", "1"),
+            ("1-2-3", "((1 - 2) - 3)"),
+            ("1+2*3+4", "((1 + (2 * 3)) + 4)"),
+            ("1*2*3", "((1 * 2) * 3)"),
+            ("2*(3-4)", "(2 * (( (3 - 4) )))"),
+        ];
+
+        for (input, expected) in cases {
+            let (node, len) = Node::from_grammar(&grammar, input).unwrap();
+            assert_eq!(len, input.len());
+            assert_eq!(nesting(&node), expected);
+        }
+    }
+
+    #[test]
+    fn indirect_left_recursion() {
+        let spec = "<a>: <b> 'x' | 'y'; <b>: <a> '-';";
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = "y-x-x";
+        let (node, len) = Node::from_grammar(&grammar, input).unwrap();
+        assert_eq!(len, input.len());
+        assert_eq!(nesting(&node), "((((y -) x) -) x)");
+    }
+
+    #[test]
+    fn nullable_repetition() {
+        let spazio = Rule {
+            name: "spazio",
+            rule: RuleOrs(vec![
+                RuleList(vec![RulePiece {
+                    content: RulePieceContent::Literal(" ".into()),
+                    repetition: Repetition::RepeatTogether,
+                }]),
+                RuleList(vec![RulePieceContent::Literal("".into()).into()]),
+            ]),
+        };
+        let spazi = Rule {
+            name: "spazi",
+            rule: RuleOrs(vec![RuleList(vec![RulePiece {
+                content: RulePieceContent::Rule(1),
+                repetition: Repetition::RepeatSeparate,
+            }])]),
+        };
+
+        // the grammar compiler would reject this
+        let mut rules_map = FxHashMap::default();
+        rules_map.insert(0, spazi);
+        rules_map.insert(1, spazio);
+        let grammar = Grammar {
+            rules: rules_map,
+            ..Default::default()
+        };
+
+        let (_, len) = Node::from_grammar(&grammar, "   x").unwrap();
+        assert_eq!(len, 3);
+    }
+
+    #[test]
+    fn memoization() {
+        // without the memo table, every level parses <e> three times
+        let spec = r#"
+<s>: <e> '!' | <e> '?' | <e>;
+<e>: '(' <s> ')' | 'x';"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let depth = 30;
+        let input = format!("{}x{}", "(".repeat(depth), ")".repeat(depth));
+
+        let options = ParseOptions::default();
+        let (_, len, stats) = Node::from_grammar_with_stats(&grammar, &input, &options).unwrap();
+        assert_eq!(len, input.len());
+        assert_eq!(stats.entries, 2 * (depth + 1));
+        assert!(stats.hits > 0);
+
+        let input = "((x!)?)";
+        let memoized = Node::from_grammar_with_options(&grammar, input, &options).unwrap();
+        let options = ParseOptions {
+            memoize: false,
+            ..Default::default()
+        };
+        let (node, len, stats) = Node::from_grammar_with_stats(&grammar, input, &options).unwrap();
+        assert_eq!((node, len), memoized);
+        assert_eq!(stats, MemoStats::default());
+    }
+
+    #[test]
+    fn limits() {
+        // takes exponential time without the memo table
+        let spec = r#"
+<s>: <e> '!' | <e> '?' | <e>;
+<e>: '(' <s> ')' | 'x';"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        let input = format!("{}x{}", "(".repeat(30), ")".repeat(30));
+
+        let cancel = Arc::new(AtomicBool::new(true));
+        let limited = [
+            (Some(1000), None, None, Limit::Steps(1000)),
+            (None, Some(Instant::now()), None, Limit::Deadline),
+            (None, None, Some(cancel), Limit::Cancelled),
+        ];
+
+        for (max_steps, deadline, cancel, limit) in limited {
+            let options = ParseOptions {
+                memoize: false,
+                max_steps,
+                deadline,
+                cancel,
+                ..Default::default()
+            };
+
+            let err = Node::from_grammar_with_options(&grammar, &input, &options).unwrap_err();
+            assert!(matches!(err, ParseError::Stopped(got) if got == limit));
+        }
+    }
+
+    #[test]
+    fn depth() {
+        let spec = "<lista>: '[' <lista> ']' | 'x';";
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let options = ParseOptions {
+            max_depth: Some(128),
+            ..Default::default()
+        };
+
+        let input = format!("{}x{}", "[".repeat(100_000), "]".repeat(100_000));
+        let err = Node::from_grammar_with_options(&grammar, &input, &options).unwrap_err();
+        assert!(matches!(err, ParseError::Stopped(Limit::Depth(128))));
+
+        let input = format!("{}x{}", "[".repeat(100), "]".repeat(100));
+        let (_, len) = Node::from_grammar_with_options(&grammar, &input, &options).unwrap();
+        assert_eq!(len, input.len());
+
+        // without a limit, nothing that goes through the tree runs out of stack
+        let input = format!("{}x{}", "[".repeat(100_000), "]".repeat(100_000));
+        let (tree, len) = Node::from_grammar(&grammar, &input).unwrap();
+        assert_eq!(len, input.len());
+        assert!(tree
+            .to_string()
+            .starts_with(r#"{"name": "lista", "content": [{"name": "[""#));
+        assert!(tree.with_spans().to_string().ends_with("}]}"));
+
+        let forest = Forest::from_grammar(&grammar, &input).unwrap();
+        assert_eq!(forest.count_trees(), 1);
+        assert_eq!(forest.trees(), vec![tree]);
+
+        let spec = "<parole>: <parola> ' ' <parole> | <parola>; <parola>: [a-z]+;";
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = vec!["ciao"; 2000].join(" ");
+        let (_, len) = Node::from_grammar(&grammar, &input).unwrap();
+        assert_eq!(len, input.len());
+    }
+
+    #[test]
+    fn strict() {
+        let spec = "<somma>: [0-9] | [0-9] '+' <somma>;";
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let input = "1+2\n+3";
+        let (_, len) = Node::from_grammar(&grammar, input).unwrap();
+        assert_eq!(len, 3);
+
+        let err = Node::from_grammar_strict(&grammar, input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "while parsing somma: expected the end of the input at line 1, column 4, found \"\n+3\""
+        );
+        assert!(Node::from_grammar_strict(&grammar, "1+2+3").is_ok());
+    }
+
+    #[test]
+    fn failure_after_the_end() {
+        let spec = "<somma>: [0-9] | [0-9] '+' <somma>;";
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        // the parser got past the end of what the main rule matched
+        let err = Node::from_grammar_strict(&grammar, "1+2+x").unwrap_err();
+        assert!(matches!(err, ParseError::Unexpected { offset: 4, .. }));
+    }
+
+    #[test]
+    fn recovery() {
+        let spec = r#"
+<programma>: <istruzione>*;
+<istruzione>: <nome> '=' <valore> ';';
+<valore>: <nome> | <stringa>;
+<nome>: [a-z]+;
+<stringa>: '"' [0-z]* '"';
+<spazio>: ' '+;
+@skip <spazio>;
+@atomic <nome> <stringa>;
+@sync ';';"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        // the ';' in the string doesn't end the broken statement
+        let input = "a = b; c \"d;e\"; f = g;";
+        let (node, errors) = Node::from_grammar_recovering(&grammar, input);
+        assert_eq!(nesting(&node), "((a = b ;) !c \"d;e\";! (f = g ;))");
+        assert_eq!(errors.len(), 1);
+
+        let input = "a = b; c = ; d = e; f g; h";
+        let (node, errors) = Node::from_grammar_recovering(&grammar, input);
+        assert_eq!(nesting(&node), "(((a = b ;) !c = ;! (d = e ;) !f g;!) !h!)");
+
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|err| err.to_string().lines().next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 1, column 12: expected one of: <nome> <stringa>",
+                "line 1, column 23: expected '='",
+                "line 1, column 27: expected '='",
+            ]
+        );
+    }
+
+    #[test]
+    fn furthest_failure() {
+        let spec = r#"
+<oggetto>: '{' '}' | '{' <coppia> '}' | '{' <coppia> <altre>* '}';
+@skip <spazio>;
+@atomic <stringa>;
+<altre>: ',' <coppia>;
+<coppia>: <stringa> ':' <stringa>;
+<stringa>: '"' [a-z]+ '"';
+<spazio>: ' '+;"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let cases = [
+            (
+                r#"{ "a": "b" "c": "d" }"#,
+                "line 1, column 12: expected one of: ',' '}'",
+            ),
+            (r#"{ "a": 3 }"#, "line 1, column 8: expected <stringa>"),
+            (r#"{ "a": "b }"#, "line 1, column 10: expected '\"'"),
+        ];
+
+        for (input, expected) in cases {
+            let err = Node::from_grammar(&grammar, input).unwrap_err();
+            let first_line = err.to_string().lines().next().unwrap().to_string();
+            assert_eq!(first_line, expected);
+        }
+    }
+
+    #[test]
+    fn choice() {
+        let rules = r#"
+<frase>: <saluto> <nome>;
+<saluto>: 'ciao' | 'ciao ';
+<nome>: [a-z]+ | [a-z]+;"#;
+        let input = "ciao mondo";
+
+        let parse = |directive: &str| {
+            let spec = format!("{}{}", directive, rules);
+            let (spec, _) = SpecGrammar::parse_len(&spec).unwrap();
+            let grammar = Grammar::try_from(&spec).unwrap();
+
+            Node::from_grammar(&grammar, input)
+                .map(|(_, len)| len)
+                .map_err(|err| (err.is_fatal(), err.to_string()))
+        };
+
+        assert_eq!(parse(""), Ok(input.len()));
+        // the first alternative doesn't match the space, and then <nome> can't match
+        assert!(matches!(parse("@choice first <saluto>;"), Err((false, _))));
+        assert_eq!(
+            parse("@choice unambiguous;"),
+            Err((
+                true,
+                "while parsing nome: alternatives 1 and 2 both match \"mondo\"".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let spec = r#"
+<istruzione>: 'if' <nome> | 'while' <nome> | <nome> '=' <nome>;
+@keywords <nome> 'if' 'while';
+@skip <spazio>;
+<nome>: [a-z]+;
+<spazio>: ' '+;"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let cases = [
+            ("if x", true),
+            ("ifx = y", true),
+            ("x = whiley", true),
+            ("if = y", false),
+            ("x = while", false),
+            ("whilex", false),
+        ];
+
+        for (input, matches) in cases {
+            let parsed = Node::from_grammar(&grammar, input);
+            let full = matches!(parsed, Ok((_, len)) if len == input.len());
+            assert_eq!(full, matches, "{}", input);
+        }
+    }
+
+    #[test]
+    fn operators() {
+        let spec = r#"
+<espressione>: <numero> | '(' <espressione> ')';
+@operators <espressione>
+    | left 1 '+' '-'
+    | left 2 '*'
+    | right 3 '^'
+    | prefix 4 '-'
+    | postfix 5 '!';
+@skip <spazio>;
+<numero>: [0-9]+;
+<spazio>: ' '+;"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let cases = [
+            ("1+2*3", "(1 + (2 * 3))"),
+            ("1 - 2 - 3", "((1 - 2) - 3)"),
+            ("2^3^4", "(2 ^ (3 ^ 4))"),
+            ("1+2*3^4^5-6!", "((1 + (2 * (3 ^ (4 ^ 5)))) - (6 !))"),
+            ("-1*2", "((- 1) * 2)"),
+            ("2*(3+4)", "(2 * (( (3 + 4) )))"),
+        ];
+
+        for (input, expected) in cases {
+            let (node, len) = Node::from_grammar(&grammar, input).unwrap();
+            assert_eq!(len, input.len());
+            assert_eq!(nesting(&node), expected);
+        }
     }
 
     // #[test]
@@ -302,4 +2003,60 @@ mod tests {
 
     //     let grammar: Grammar = rules.try_into();
     // }
+
+    #[test]
+    fn bytes() {
+        let spec = r#"
+@bytes;
+<pacchetto>: <firma> <lunghezza> <dati>;
+<firma>: 0x89 'PNG';
+<lunghezza>: 0x00-0x7F;
+<dati>: 0x00-0xFF+;"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let bytes = [0x89, b'P', b'N', b'G', 0x03, 0xFF, 0x0A, 0xC3];
+        let (node, len) = Node::from_bytes(&grammar, &bytes, &ParseOptions::default()).unwrap();
+        assert_eq!(len, bytes.len());
+
+        let dati = match &node.content {
+            NodeContent::Cons(list) => &list[2],
+            _ => panic!("expected a list"),
+        };
+        assert_eq!(
+            dati.to_string(),
+            r#"{"name": "dati", "content": [{"name": "char range", "content": ["0xFF 0x0A 0xC3"]}]}"#
+        );
+        assert_eq!((dati.span.start, dati.span.end), (5, 8));
+        assert_eq!(dati.span.end_position.line, 2);
+
+        let edit = Edit {
+            range: 5..6,
+            text: "a",
+        };
+        let err = Node::reparse(&grammar, &node, &edit, "\u{89}PNG\u{3}a\n\u{C3}").unwrap_err();
+        assert!(matches!(err, ParseError::ReparsedBytes));
+
+        let input = [0x89, 0xC3, b'N', b'G'];
+        let err = Node::from_bytes(&grammar, &input, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(err, ParseError::Unexpected { offset: 1, .. }));
+
+        // literals that would mean something else in the other mode
+        let mismatched = |spec: &'static str| {
+            let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+            match Grammar::try_from(&spec) {
+                Err(GrammarError::MismatchedLiterals(found)) => found[0].to_string(),
+                other => panic!("expected mismatched literals, got {:?}", other),
+            }
+        };
+        assert_eq!(
+            mismatched("@bytes;\n<firma>: 0x89 'PNG€';"),
+            "<firma> has 'PNG€', which isn't ASCII, and the grammar matches bytes"
+        );
+        assert_eq!(
+            mismatched("<dati>: 0x80-0xFF+;"),
+            "<dati> has 0x80-0xFF, which goes past 0x7F, and the grammar matches text"
+        );
+    }
 }
@@ -0,0 +1,55 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Settings for `Node::from_grammar_with_options`
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// remember what every rule parsed at every position, so no
+    /// rule gets parsed twice in the same place. Uses more memory,
+    /// but grammars with alternatives that start the same way
+    /// don't take exponential time
+    pub memoize: bool,
+    /// fail if the main rule doesn't match all of the input,
+    /// instead of returning the part it matched
+    pub strict: bool,
+    /// how many times rules can be tried before giving up
+    pub max_steps: Option<usize>,
+    /// when to give up
+    pub deadline: Option<Instant>,
+    /// the parse stops as soon as possible when this gets set
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// how deep rules can be inside of each other. The stack
+    /// grows as needed, so without a limit it's up to memory
+    pub max_depth: Option<usize>,
+}
+
+/// Cancel flags are only the same if they're the same flag
+impl PartialEq for ParseOptions {
+    fn eq(&self, other: &Self) -> bool {
+        let same_cancel = match (&self.cancel, &other.cancel) {
+            (Some(cancel), Some(other)) => Arc::ptr_eq(cancel, other),
+            (cancel, other) => cancel.is_none() && other.is_none(),
+        };
+
+        self.memoize == other.memoize
+            && self.strict == other.strict
+            && self.max_steps == other.max_steps
+            && self.deadline == other.deadline
+            && self.max_depth == other.max_depth
+            && same_cancel
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            memoize: true,
+            strict: false,
+            max_steps: None,
+            deadline: None,
+            cancel: None,
+            max_depth: None,
+        }
+    }
+}
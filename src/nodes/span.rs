@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use super::input::Input;
+use super::node::Node;
+use super::node::NodeContent;
+use crate::utils::Position;
+
+/// Where a node is in the input: a range of bytes, and the
+/// line and column where it starts and ends. Skipped text
+/// before and after a node isn't part of it
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize)]
+pub struct Span {
+    pub start: usize,
+    /// the first byte after the node
+    pub end: usize,
+    pub start_position: Position,
+    pub end_position: Position,
+}
+
+impl<'g, 'i> Node<'g, 'i> {
+    /// Fills in the span of the node and of all of its children.
+    /// `input` must be what they were parsed from
+    pub fn locate(&mut self, input: impl Into<Input<'i>>) {
+        self.locate_after(input, &mut 0, (0, Position { line: 1, column: 1 }));
+    }
+
+    /// Like `locate`, for a node that comes after text that's been
+    /// located already. `cursor` is where that text ends, and `known`
+    /// an offset before the node with its position, so lines aren't
+    /// counted again from the start. Returns whether the node has
+    /// any text in it
+    pub fn locate_after(
+        &mut self,
+        input: impl Into<Input<'i>>,
+        cursor: &mut usize,
+        known: (usize, Position),
+    ) -> bool {
+        let input = input.into();
+        let located = self.locate_offsets(input, cursor);
+
+        let mut offsets = Vec::new();
+        self.offsets(&mut offsets);
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        let positions = input.positions_after(known, &offsets);
+        self.locate_positions(&offsets, &positions);
+        located
+    }
+
+    /// Sets the byte ranges, and returns whether the node has any
+    /// text in it. Nodes without text are put where the text
+    /// before them ends, that's what `cursor` is
+    fn locate_offsets(&mut self, input: Input<'i>, cursor: &mut usize) -> bool {
+        // the range of the text in the nodes we're in, and in the tree
+        let mut ranges: Vec<Option<(usize, usize)>> = vec![None];
+
+        self.walk_mut(|node, entering| {
+            let (start, end) = match (&node.content, entering) {
+                (NodeContent::Cons(_), true) => {
+                    ranges.push(None);
+                    return;
+                }
+                (NodeContent::Cons(_), false) => match ranges.pop().expect("entered") {
+                    Some(range) => range,
+                    None => {
+                        node.span.start = *cursor;
+                        node.span.end = *cursor;
+                        return;
+                    }
+                },
+                (NodeContent::Literal(text) | NodeContent::Error(text), _) => {
+                    let start = input.offset(text.as_bytes());
+                    *cursor = start + text.len();
+                    (start, *cursor)
+                }
+                (NodeContent::Bytes(bytes), _) => {
+                    let start = input.offset(bytes);
+                    *cursor = start + bytes.len();
+                    (start, *cursor)
+                }
+            };
+
+            node.span.start = start;
+            node.span.end = end;
+            let range = ranges.last_mut().expect("the tree's range");
+            *range = Some(range.map_or((start, end), |(start, _)| (start, end)));
+        });
+
+        ranges[0].is_some()
+    }
+
+    fn offsets(&self, offsets: &mut Vec<usize>) {
+        let mut to_visit = vec![self];
+
+        while let Some(node) = to_visit.pop() {
+            offsets.push(node.span.start);
+            offsets.push(node.span.end);
+
+            if let NodeContent::Cons(list) = &node.content {
+                to_visit.extend(list.iter().map(|node| node.as_ref()));
+            }
+        }
+    }
+
+    fn locate_positions(&mut self, offsets: &[usize], positions: &[Position]) {
+        let position = |offset| positions[offsets.binary_search(&offset).unwrap()];
+
+        self.walk_mut(|node, entering| {
+            if entering {
+                node.span.start_position = position(node.span.start);
+                node.span.end_position = position(node.span.end);
+            }
+        });
+    }
+
+    /// Calls `visit` with every node in input order, with `true`, and
+    /// with the nodes that have children once more after them, with
+    /// `false`. Children are taken out of their parent while they're
+    /// gone through, so the tree is gone through with a stack instead
+    /// of recursing, since it can be as deep as the input is long
+    fn walk_mut(&mut self, mut visit: impl FnMut(&mut Self, bool)) {
+        visit(self, true);
+        // the children of the nodes we're in, and how many were visited
+        let mut stack = match &mut self.content {
+            NodeContent::Cons(list) => vec![(std::mem::take(list), 0)],
+            _ => return,
+        };
+
+        while let Some((list, next)) = stack.last_mut() {
+            if *next == list.len() {
+                let (list, _) = stack.pop().expect("it's the top");
+                let parent = match stack.last_mut() {
+                    Some((list, next)) => {
+                        let parent = Arc::make_mut(&mut list[*next]);
+                        *next += 1;
+                        parent
+                    }
+                    None => &mut *self,
+                };
+
+                parent.content = NodeContent::Cons(list);
+                visit(parent, false);
+                continue;
+            }
+
+            let node = Arc::make_mut(&mut list[*next]);
+            visit(node, true);
+            match &mut node.content {
+                NodeContent::Cons(children) => {
+                    let children = std::mem::take(children);
+                    stack.push((children, 0));
+                }
+                _ => *next += 1,
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use rustc_hash::FxHashMap;
+
+use super::error::Limit;
+use super::error::ParseError;
+use super::input::Input;
+use super::memo::Memo;
+use super::memo::MemoKey;
+use super::node::Node;
+use super::options::ParseOptions;
+use super::trace::Trace;
+use crate::rules::grammar::Grammar;
+use crate::rules::literal::Literal;
+use crate::rules::literal::LiteralContent;
+
+/// A left recursive rule being parsed at some position, see
+/// `Node::grow_rule`. Positions are the length of the input
+/// left, and we keep track of whether we're in an atomic rule
+/// because then the rule could parse differently
+pub type GrowingKey<'g> = (&'g str, usize, bool);
+
+/// The furthest position where something failed to match,
+/// and everything that was expected there
+#[derive(Debug, Default)]
+pub struct Furthest<'i> {
+    /// the input left at that position
+    pub rest: Option<&'i [u8]>,
+    pub expected: BTreeSet<String>,
+}
+
+impl<'i> Furthest<'i> {
+    pub fn record(&mut self, rest: &'i [u8], expected: String) {
+        match self.rest {
+            Some(furthest) if furthest.len() < rest.len() => return,
+            Some(furthest) if furthest.len() == rest.len() => {}
+            _ => {
+                self.rest = Some(rest);
+                self.expected.clear();
+            }
+        }
+
+        self.expected.insert(expected);
+    }
+
+    /// Keeps the furthest of the two failures, or both if
+    /// they happened in the same place
+    pub fn merge(&mut self, other: Self) {
+        if let Some(rest) = other.rest {
+            for expected in other.expected {
+                self.record(rest, expected);
+            }
+        }
+    }
+
+    /// Where the furthest failure happened, in `input`
+    pub fn offset(&self, input: Input) -> Option<usize> {
+        self.rest.map(|rest| input.len() - rest.len())
+    }
+}
+
+/// Everything the parsing functions in `Node` need to
+/// carry around while walking the grammar
+pub struct ParseState<'g, 'i> {
+    pub gram: &'g Grammar<'g>,
+    /// all of the input, for positions in errors, and
+    /// to make the leaves of the tree from what's matched
+    pub input: Input<'i>,
+    /// how many atomic rules we are currently inside of.
+    /// when it's not zero nothing gets skipped
+    pub atomic_depth: usize,
+    /// the best match found until now for the left recursive
+    /// rules we're in the middle of parsing
+    pub growing: FxHashMap<GrowingKey<'g>, Option<(Node<'g, 'i>, usize)>>,
+    pub memo: Memo<'g, 'i>,
+    pub furthest: Furthest<'i>,
+    /// the outermost atomic rule we're in, and the input left where
+    /// it started. If it fails right there, we report the rule as
+    /// expected, and not the characters it's made of
+    pub token: Option<(&'g str, usize)>,
+    /// whether failed repetitions become error nodes,
+    /// see `Node::from_grammar_recovering`
+    pub recovering: bool,
+    /// the errors we recovered from
+    pub errors: Vec<ParseError<'g, 'i>>,
+    /// what the rules parsed before an edit, see `Node::reparse`
+    pub reusable: FxHashMap<MemoKey<'g>, (Arc<Node<'g, 'i>>, usize)>,
+    /// how many times rules were tried
+    pub steps: usize,
+    pub max_steps: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// how many rules we're inside of
+    pub depth: usize,
+    pub max_depth: Option<usize>,
+    /// whether the input is only the beginning of the text,
+    /// see `Node::from_rule_prefix`
+    pub more: bool,
+    /// whether a literal needed the text after the input
+    pub reached_end: bool,
+    /// every rule tried, see `Node::from_grammar_traced`
+    pub trace: Option<Trace<'g>>,
+    /// where the skip rules last ran, where they stopped, and
+    /// how far they looked, as lengths of the input left
+    pub skipped: Option<(usize, usize, usize)>,
+    /// how far the rules we're in looked, see `look`
+    pub looked: usize,
+}
+
+/// Looking at the clock takes longer than trying a rule,
+/// so it's done once every this many steps
+const CLOCK_STEPS: usize = 256;
+
+impl<'g, 'i> ParseState<'g, 'i> {
+    /// A state for parsing all of `input`, which
+    /// positions in errors and spans count from
+    pub fn with_input(gram: &'g Grammar<'g>, input: Input<'i>, options: &ParseOptions) -> Self {
+        Self {
+            gram,
+            input,
+            atomic_depth: 0,
+            growing: FxHashMap::default(),
+            memo: Memo::new(options.memoize),
+            furthest: Furthest::default(),
+            token: None,
+            recovering: false,
+            errors: Vec::new(),
+            reusable: FxHashMap::default(),
+            steps: 0,
+            max_steps: options.max_steps,
+            deadline: options.deadline,
+            cancel: options.cancel.clone(),
+            depth: 0,
+            max_depth: options.max_depth,
+            more: false,
+            reached_end: false,
+            trace: None,
+            skipped: None,
+            looked: usize::MAX,
+        }
+    }
+
+    /// Records that `expected` was expected where `rest` starts
+    pub fn expected(&mut self, rest: &'i [u8], expected: String) {
+        match self.token {
+            Some((name, len)) if len == rest.len() => {
+                self.furthest.record(rest, format!("<{}>", name))
+            }
+            _ => self.furthest.record(rest, expected),
+        }
+    }
+
+    /// Records if matching `literal` at the start of `rest`
+    /// could go differently with the text after the input
+    pub fn peek(&mut self, literal: &Literal, rest: &[u8], repeated: bool) {
+        if !self.more || self.reached_end {
+            return;
+        }
+
+        // a keyword can't be followed by a word character
+        let keyword = matches!(literal.content, LiteralContent::Str(word) if word.as_bytes() == rest && self.gram.is_keyword(word));
+        self.reached_end = keyword || literal.needs_more(rest, repeated);
+    }
+
+    /// Records that `len` bytes at the start of `rest` were looked at,
+    /// or the end of the input if there's less than that. `looked` is
+    /// the length of the input left at the furthest one, which is 0
+    /// for the end of the input: text added there can change what
+    /// the rules that looked at it parse, see `Node::reparse`
+    pub fn look(&mut self, rest: &[u8], len: usize) {
+        if len > 0 {
+            self.looked = self.looked.min((rest.len() + 1).saturating_sub(len));
+        }
+    }
+
+    /// Starts tracing a try at `rule`, or at one
+    /// of its alternatives, where `rest` starts
+    pub fn trace_start(
+        &mut self,
+        rule: &'g str,
+        alternative: Option<usize>,
+        rest: &[u8],
+    ) -> Option<usize> {
+        let input = self.input;
+        let trace = self.trace.as_mut()?;
+        trace.start(rule, alternative, input.len() - rest.len())
+    }
+
+    pub fn trace_finish<T>(
+        &mut self,
+        attempt: Option<usize>,
+        parsed: &Result<(T, usize), ParseError<'g, 'i>>,
+    ) {
+        if let Some(trace) = &mut self.trace {
+            trace.finish(attempt, parsed.as_ref().ok().map(|(_, len)| *len));
+        }
+    }
+
+    /// Counts a rule being tried, failing if the parse
+    /// went on for too long, or was cancelled
+    pub fn step(&mut self) -> Result<(), ParseError<'g, 'i>> {
+        self.count_step().map_err(ParseError::Stopped)
+    }
+
+    /// Like `step`, for parsers that have their own errors
+    pub fn count_step(&mut self) -> Result<(), Limit> {
+        self.steps += 1;
+
+        if let Some(max) = self.max_steps {
+            if self.steps > max {
+                return Err(Limit::Steps(max));
+            }
+        }
+
+        if let Some(cancel) = &self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Limit::Cancelled);
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(CLOCK_STEPS) && Instant::now() >= deadline {
+                return Err(Limit::Deadline);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Goes one rule deeper, failing if that's too deep.
+    /// Coming back out is up to the caller
+    pub fn enter(&mut self) -> Result<(), ParseError<'g, 'i>> {
+        if let Some(max) = self.max_depth {
+            if self.depth >= max {
+                return Err(ParseError::Stopped(Limit::Depth(max)));
+            }
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// What a rule parsed before an edit, if it can be used again
+    pub fn reused(&self, key: &MemoKey<'g>) -> Option<(Arc<Node<'g, 'i>>, usize)> {
+        match self.depends_on_growing(key.1) {
+            true => None,
+            false => self.reusable.get(key).cloned(),
+        }
+    }
+
+    /// Whether what gets parsed at this position could use the seed
+    /// of a left recursive rule that's still growing, and so could
+    /// parse differently once it's done
+    pub fn depends_on_growing(&self, input_len: usize) -> bool {
+        self.growing.keys().any(|(_, len, _)| *len <= input_len)
+    }
+}
@@ -0,0 +1,279 @@
+use std::io;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::str;
+
+use thiserror::Error;
+
+use super::error::ParseError;
+use super::input::Input;
+use super::node::Node;
+use super::options::ParseOptions;
+use super::state::ParseState;
+use crate::rules::grammar::Grammar;
+use crate::rules::rule::Rule;
+
+/// How long a record can be, by default
+pub const DEFAULT_MAX_RECORD: usize = 1 << 20;
+
+/// How much gets read at least, when a record needs more input.
+/// Otherwise as much as there is already, so that a long record
+/// doesn't get parsed again for every chunk of it
+const CHUNK: usize = 8 * 1024;
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("can't read the input: {0}")]
+    Io(#[from] io::Error),
+    #[error("the input isn't valid UTF-8 at byte {0}")]
+    Utf8(usize),
+    #[error("the record at byte {offset} is longer than {max} bytes")]
+    TooLong { offset: usize, max: usize },
+    #[error("the record at byte {0} matches no input")]
+    Empty(usize),
+    /// a record that didn't parse, with where it starts
+    /// in the stream. Positions in `message` are in the record
+    #[error("in the record at byte {offset}: {message}")]
+    Parse { offset: usize, message: String },
+}
+
+/// Parses matches of a rule from a reader, one after the other,
+/// keeping in memory only the input of the one being parsed
+pub struct Records<'g, R> {
+    gram: &'g Grammar<'g>,
+    rule: &'g Rule<'g>,
+    reader: R,
+    pub options: ParseOptions,
+    /// how many bytes a record can take, and so
+    /// about how much of the input is kept around
+    pub max_len: usize,
+    /// what was read. Records get taken out of it only
+    /// when reading more, so it isn't moved for each of them
+    buffer: Vec<u8>,
+    /// where what isn't part of a record yet starts in the buffer
+    start: usize,
+    /// where that is in the stream
+    offset: usize,
+    eof: bool,
+}
+
+impl<'g, R: Read> Records<'g, R> {
+    pub fn new(gram: &'g Grammar<'g>, rule: &'g Rule<'g>, reader: R) -> Self {
+        Self {
+            gram,
+            rule,
+            reader,
+            options: ParseOptions::default(),
+            max_len: DEFAULT_MAX_RECORD,
+            buffer: Vec::new(),
+            start: 0,
+            offset: 0,
+            eof: false,
+        }
+    }
+
+    /// Calls `record` with the tree of every record, and where its
+    /// input starts in the stream. Spans are in the record's input,
+    /// which gets dropped once `record` returns. Stops at the end of
+    /// the input, or when `record` breaks, and returns how many
+    /// records there were. Calling it again goes on from there
+    pub fn for_each(
+        &mut self,
+        mut record: impl FnMut(&Node<'g, '_>, usize) -> ControlFlow<()>,
+    ) -> Result<usize, StreamError> {
+        let mut count = 0;
+
+        loop {
+            let (eof, offset) = (self.eof, self.offset);
+            let text = valid_text(&self.buffer[self.start..], eof, offset)?;
+            if eof {
+                let input = Input::new(self.gram, text);
+                let mut state = ParseState::with_input(self.gram, input, &ParseOptions::default());
+                if Node::skipped_len(&mut state, text) == text.len() {
+                    return Ok(count);
+                }
+            }
+
+            let parsed = Node::from_rule_prefix(self.gram, self.rule, text, &self.options, !eof)
+                .map_err(|err| parse_error(err, offset))?;
+
+            let found = match &parsed {
+                Some((_, 0)) => return Err(StreamError::Empty(offset)),
+                Some((node, len)) => Some((*len, record(node, offset))),
+                None => None,
+            };
+            // the tree borrows the buffer, which could get more input
+            drop(parsed);
+
+            let (len, flow) = match found {
+                Some(found) => found,
+                None if self.buffer.len() - self.start < self.max_len => {
+                    self.read_more()?;
+                    continue;
+                }
+                None => return Err(self.too_long(text, offset)),
+            };
+
+            self.start += len;
+            self.offset += len;
+
+            count += 1;
+            if flow.is_break() {
+                return Ok(count);
+            }
+        }
+    }
+
+    /// Drops the records from the buffer, and reads at least as much
+    /// as is left in it, but not more than a record can take
+    fn read_more(&mut self) -> Result<(), StreamError> {
+        self.buffer.drain(..self.start);
+        self.start = 0;
+
+        let left = self.buffer.len();
+        let len = left.max(CHUNK).min(self.max_len - left);
+        self.buffer.reserve(len);
+
+        let read = (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut self.buffer)?;
+        self.eof = read == 0;
+        Ok(())
+    }
+
+    /// The error for a record that doesn't fit in the buffer,
+    /// unless it fails to parse before the end of it
+    fn too_long(&self, text: &str, offset: usize) -> StreamError {
+        match Node::from_rule_prefix(self.gram, self.rule, text, &self.options, false) {
+            Err(ParseError::Unexpected { offset: end, .. }) if end == text.len() => {}
+            Err(err) => return parse_error(err, offset),
+            Ok(_) => {}
+        }
+
+        StreamError::TooLong {
+            offset,
+            max: self.max_len,
+        }
+    }
+}
+
+fn parse_error(err: ParseError, offset: usize) -> StreamError {
+    StreamError::Parse {
+        offset,
+        message: err.to_string(),
+    }
+}
+
+/// The buffer as text, without a character cut
+/// in half by the end of what was read
+fn valid_text(buffer: &[u8], eof: bool, offset: usize) -> Result<&str, StreamError> {
+    match str::from_utf8(buffer) {
+        Ok(text) => Ok(text),
+        Err(err) if !eof && err.error_len().is_none() => {
+            Ok(str::from_utf8(&buffer[..err.valid_up_to()]).unwrap())
+        }
+        Err(err) => Err(StreamError::Utf8(offset + err.valid_up_to())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::node::NodeContent;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node as _;
+
+    /// Counts how many times it's read from
+    struct Counted<'a> {
+        text: &'a [u8],
+        reads: usize,
+    }
+
+    impl Read for Counted<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.text.read(buf)
+        }
+    }
+
+    #[test]
+    fn records() {
+        let spec = "
+<tutto>: <riga>*;
+<riga>: <nome> '=' <valore> ';' | 'fine' ';';
+<valore>: <valore> '+' <nome> | <nome>;
+<nome>: [a-z]+;
+<spazio>: [\t- ]+;
+@skip <spazio>;
+@atomic <nome>;"
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        let riga = grammar.get_named("riga").unwrap();
+
+        // long enough to be read a few times, cutting records in half
+        let input: String = (0..3000)
+            .map(|i| match i % 10 {
+                0 => "fine ;\n".to_string(),
+                _ => format!(
+                    "{} = b + {};\n",
+                    "a".repeat(i % 7 + 1),
+                    "c".repeat(i % 5 + 1)
+                ),
+            })
+            .collect();
+
+        let (tree, _) = Node::from_grammar(&grammar, &input).unwrap();
+        let expected: Vec<_> = match &tree.content {
+            NodeContent::Cons(list) => match &list[0].content {
+                NodeContent::Cons(items) => items.iter().map(|item| item.to_string()).collect(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        let mut found = Vec::new();
+        let mut records = Records::new(&grammar, riga, input.as_bytes());
+        let count = records
+            .for_each(|node, offset| {
+                let text = &input[offset + node.span.start..offset + node.span.end];
+                assert!(text.ends_with(';'));
+                found.push(node.to_string());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(count, 3000);
+        assert_eq!(found, expected);
+
+        let mut records = Records::new(&grammar, riga, "a = b;\nc = ;\n".as_bytes());
+        let stopped = records.for_each(|_, _| ControlFlow::Break(()));
+        assert_eq!(stopped.unwrap(), 1);
+        let failed = records.for_each(|_, _| ControlFlow::Continue(()));
+        assert!(matches!(failed, Err(StreamError::Parse { offset: 6, .. })));
+
+        // a long record is read in bigger and bigger pieces,
+        // not parsed again for every few kilobytes of it
+        let long = format!("a = {};", "b".repeat(1 << 20));
+        let mut reader = Counted {
+            text: long.as_bytes(),
+            reads: 0,
+        };
+        let mut records = Records::new(&grammar, riga, &mut reader);
+        records.max_len = 2 << 20;
+        let count = records.for_each(|_, _| ControlFlow::Continue(()));
+        assert_eq!(count.unwrap(), 1);
+        assert!(reader.reads < 50, "{} reads", reader.reads);
+
+        let long = format!("a = {};", "b".repeat(20000));
+        let mut records = Records::new(&grammar, riga, long.as_bytes());
+        records.max_len = 100;
+        let failed = records.for_each(|_, _| ControlFlow::Continue(()));
+        assert!(matches!(
+            failed,
+            Err(StreamError::TooLong {
+                offset: 0,
+                max: 100
+            })
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use super::input::Input;
+use crate::utils::Position;
+
+/// One try at parsing a rule, or one of its alternatives
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attempt<'g> {
+    /// how many traced attempts it's inside of
+    pub depth: usize,
+    pub rule: &'g str,
+    /// which alternative of the rule, counting from 1,
+    /// or `None` for the rule itself
+    pub alternative: Option<usize>,
+    pub offset: usize,
+    pub position: Position,
+    /// how much input it matched, or `None` if it failed
+    pub matched: Option<usize>,
+}
+
+/// The rules and alternatives tried while parsing, in the
+/// order they were tried, see `Node::from_grammar_traced`
+#[derive(Debug, Default)]
+pub struct Trace<'g> {
+    /// the rules to trace, or all of them if it's empty
+    rules: Vec<String>,
+    pub attempts: Vec<Attempt<'g>>,
+    depth: usize,
+}
+
+/// Shows a trace as JSON, one attempt per line
+pub struct JsonLines<'t, 'g>(&'t Trace<'g>);
+
+impl<'g> Trace<'g> {
+    /// A trace of `rules`, and of all of them if there's none
+    pub fn new(rules: Vec<String>) -> Self {
+        Self {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    /// Records that `rule` is being tried at `offset`. Returns
+    /// the attempt to finish, if the rule is traced
+    pub fn start(
+        &mut self,
+        rule: &'g str,
+        alternative: Option<usize>,
+        offset: usize,
+    ) -> Option<usize> {
+        if !self.rules.is_empty() && !self.rules.iter().any(|traced| traced == rule) {
+            return None;
+        }
+
+        self.attempts.push(Attempt {
+            depth: self.depth,
+            rule,
+            alternative,
+            offset,
+            position: Position::default(),
+            matched: None,
+        });
+        self.depth += 1;
+
+        Some(self.attempts.len() - 1)
+    }
+
+    pub fn finish(&mut self, attempt: Option<usize>, matched: Option<usize>) {
+        if let Some(attempt) = attempt {
+            self.attempts[attempt].matched = matched;
+            self.depth -= 1;
+        }
+    }
+
+    /// Fills in the positions of the attempts in `input`
+    pub fn locate(&mut self, input: Input) {
+        let mut offsets: Vec<_> = self.attempts.iter().map(|attempt| attempt.offset).collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        let positions = input.positions(&offsets);
+        for attempt in &mut self.attempts {
+            attempt.position = positions[offsets.binary_search(&attempt.offset).unwrap()];
+        }
+    }
+
+    pub fn json_lines(&self) -> JsonLines<'_, 'g> {
+        JsonLines(self)
+    }
+}
+
+/// One attempt per line, indented by how deep it is
+impl<'g> fmt::Display for Trace<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for attempt in &self.attempts {
+            write!(f, "{}<{}>", "  ".repeat(attempt.depth), attempt.rule)?;
+            if let Some(alternative) = attempt.alternative {
+                write!(f, " #{}", alternative)?;
+            }
+
+            let position = attempt.position;
+            write!(f, " at {}:{}", position.line, position.column)?;
+            match attempt.matched {
+                Some(len) => writeln!(f, " matched {}", len)?,
+                None => writeln!(f, " failed")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'t, 'g> fmt::Display for JsonLines<'t, 'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for attempt in &self.0.attempts {
+            let optional = |number: Option<usize>| number.map_or("null".into(), |n| n.to_string());
+
+            writeln!(
+                f,
+                "{{\"depth\": {}, \"rule\": \"{}\", \"alternative\": {}, \"offset\": {}, \"line\": {}, \"column\": {}, \"matched\": {}}}",
+                attempt.depth,
+                attempt.rule,
+                optional(attempt.alternative),
+                attempt.offset,
+                attempt.position.line,
+                attempt.position.column,
+                optional(attempt.matched)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nodes::node::Node;
+    use crate::nodes::options::ParseOptions;
+    use crate::rules::grammar::Grammar;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node as _;
+
+    #[test]
+    fn trace() {
+        let spec = r#"
+<saluto>: <parola> ' ' <parola>;
+<parola>: 'ciao' | [a-z]+;"#
+            .trim();
+        let (spec, _) = SpecGrammar::parse_len(spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        let options = ParseOptions::default();
+
+        let (parsed, trace) =
+            Node::from_grammar_traced(&grammar, "ciao mondo", &options, vec!["parola".into()]);
+        assert!(parsed.is_ok());
+        let expected = "\
+<parola> at 1:1 matched 4
+  <parola> #1 at 1:1 matched 4
+  <parola> #2 at 1:1 matched 4
+<parola> at 1:6 matched 5
+  <parola> #1 at 1:6 failed
+  <parola> #2 at 1:6 matched 5
+";
+        assert_eq!(trace.to_string(), expected);
+
+        let (parsed, trace) = Node::from_grammar_traced(&grammar, "ciao 42", &options, vec![]);
+        assert!(parsed.is_err());
+        let last = trace
+            .json_lines()
+            .to_string()
+            .lines()
+            .last()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            last,
+            r#"{"depth": 3, "rule": "parola", "alternative": 2, "offset": 5, "line": 1, "column": 6, "matched": null}"#
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::ops::ControlFlow;
+
+use super::node::Node;
+use super::node::NodeContent;
+use super::span::Span;
+
+/// Gets told about the nodes of a tree, in input order, see
+/// `Node::visit_grammar`. Rules are entered before it's known where
+/// they end, so their span comes with `exit`. Rules repeated together,
+/// like the ones in a tree, are terminals. Returning
+/// `ControlFlow::Break` stops the visit
+pub trait Visitor<'g, 'i> {
+    fn enter(&mut self, _rule: &'g str) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn terminal(&mut self, _name: &'g str, _text: &'i str, _span: &Span) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn exit(&mut self, _rule: &'g str, _span: &Span) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// How a visit went: how much input the main rule
+/// parsed, and whether the visitor stopped it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Visited {
+    pub len: usize,
+    pub stopped: bool,
+}
+
+/// A node to tell a visitor about, or a rule to exit
+/// once the nodes in it have been told about
+enum Step<'a, 'g, 'i> {
+    Visit(&'a Node<'g, 'i>),
+    Exit(&'a Node<'g, 'i>),
+}
+
+impl<'g, 'i> Node<'g, 'i> {
+    /// Tells `visitor` about the node and the ones in it, in input
+    /// order. Nodes without a name are only gone through, and error
+    /// and byte nodes are left out. The tree must be located
+    pub fn walk(&self, visitor: &mut impl Visitor<'g, 'i>) -> ControlFlow<()> {
+        let mut to_visit = vec![Step::Visit(self)];
+
+        while let Some(step) = to_visit.pop() {
+            let node = match step {
+                Step::Visit(node) => node,
+                Step::Exit(node) => {
+                    visitor.exit(node.name.unwrap(), &node.span)?;
+                    continue;
+                }
+            };
+
+            match (&node.content, node.name) {
+                (NodeContent::Literal(text), Some(name)) => {
+                    visitor.terminal(name, text, &node.span)?;
+                }
+                (NodeContent::Cons(list), name) => {
+                    if let Some(name) = name {
+                        visitor.enter(name)?;
+                        to_visit.push(Step::Exit(node));
+                    }
+                    to_visit.extend(list.iter().rev().map(|node| Step::Visit(node)));
+                }
+                _ => {}
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::error::ParseError;
+    use crate::nodes::options::ParseOptions;
+    use crate::rules::grammar::Grammar;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node as _;
+
+    /// Writes down every event, and stops at `stop_at`
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        stop_at: Option<&'static str>,
+    }
+
+    impl<'g, 'i> Visitor<'g, 'i> for Recorder {
+        fn enter(&mut self, rule: &'g str) -> ControlFlow<()> {
+            self.events.push(format!("enter {}", rule));
+            ControlFlow::Continue(())
+        }
+
+        fn terminal(&mut self, name: &'g str, text: &'i str, span: &Span) -> ControlFlow<()> {
+            self.events.push(format!("{} {:?} {:?}", name, text, span));
+            match self.stop_at == Some(text) {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        }
+
+        fn exit(&mut self, rule: &'g str, span: &Span) -> ControlFlow<()> {
+            self.events.push(format!("exit {} {:?}", rule, span));
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn visit() {
+        let rules = "
+<istruzione>: <nome> '=' <valore> ';' | <nome> '=' <espressione> ';';
+<blocco>: '{' <istruzione>* '}';
+<valore>: <valore> ',' <nome> | <nome>;
+<espressione>: <numero> | '(' <espressione> ')';
+@operators <espressione> | left 1 '+' | prefix 2 '-';
+<nome>: [a-z]+;
+<numero>: [0-9]+;
+<spazio>: [\t- ]+;
+@skip <spazio>;
+@atomic <nome> <numero>;";
+
+        // only items, pieces and items, and more than one alternative
+        let cases = [
+            (
+                "<programma>: <istruzione>*;",
+                "a = b, c;\nd = -1 + (2 + 3);\ne = 4 +;",
+            ),
+            (
+                "<programma>: 'inizio' <istruzione>* <blocco>* 'fine';",
+                "inizio a = b; { c = d; e = 1; } {f = g;} fine",
+            ),
+            (
+                "<programma>: <blocco>* | <istruzione>*;",
+                "{ a = b; } { c = (1); }",
+            ),
+        ];
+
+        for (main, input) in cases {
+            let spec = format!("{}{}", main, rules);
+            let (spec, _) = SpecGrammar::parse_len(&spec).unwrap();
+            let grammar = Grammar::try_from(&spec).unwrap();
+
+            for memoize in [true, false] {
+                let options = ParseOptions {
+                    memoize,
+                    ..Default::default()
+                };
+
+                let (tree, len) =
+                    Node::from_grammar_with_options(&grammar, input, &options).unwrap();
+                let mut expected = Recorder::default();
+                let _ = tree.walk(&mut expected);
+
+                let mut recorder = Recorder::default();
+                let visited =
+                    Node::visit_grammar(&grammar, input, &options, &mut recorder).unwrap();
+                assert_eq!(
+                    visited,
+                    Visited {
+                        len,
+                        stopped: false
+                    }
+                );
+                assert_eq!(recorder.events, expected.events, "{}", main);
+            }
+        }
+
+        // the parser stops with the visitor, before getting to the error,
+        // unless it's in the same item of the main rule
+        let spec = format!("{}{}", cases[1].0, rules);
+        let (spec, _) = SpecGrammar::parse_len(&spec).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        for (input, stop_at, len) in [
+            ("inizio a = b, c;\nd = = =", "c", 16),
+            ("inizio x = y; { a = b; } { c = d; e = = }", "b", 24),
+            ("inizio x = y; { a = b; c = d; e = = }", "b", 0),
+        ] {
+            let parsed = Node::from_grammar_with_options(&grammar, input, &options);
+            assert!(matches!(parsed, Err(ParseError::Unexpected { .. })));
+
+            let mut recorder = Recorder {
+                stop_at: Some(stop_at),
+                ..Default::default()
+            };
+            let visited = Node::visit_grammar(&grammar, input, &options, &mut recorder);
+            match len {
+                0 => assert!(matches!(visited, Err(ParseError::Unexpected { .. }))),
+                len => assert_eq!(visited.unwrap(), Visited { len, stopped: true }),
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use rustc_hash::FxHashSet;
+
+use crate::rules::choice::ChoiceStrategy;
+use crate::rules::grammar::Grammar;
+use crate::rules::literal::LiteralContent;
+use crate::rules::rule::RuleList;
+use crate::rules::rule::RulePiece;
+use crate::rules::rule::RulePieceContent;
+use crate::rules::rule::RuleRef;
+use crate::spec_parser::rule_piece::Repetition;
+
+/// alternatives that can match more strings than this
+/// are only compared by their structure
+const MAX_STRINGS: usize = 64;
+/// ranges longer than this aren't expanded into strings
+const MAX_RANGE: u32 = 16;
+
+/// Two alternatives of the same rule that can match the same
+/// input. Alternatives are numbered from 1, and `first` is
+/// always declared before `second`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Ambiguity<'a> {
+    pub rule: &'a str,
+    pub first: usize,
+    pub second: usize,
+    pub kind: AmbiguityKind,
+    /// how the rule picks between them
+    pub strategy: ChoiceStrategy,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AmbiguityKind {
+    /// they match exactly the same strings
+    Identical,
+    /// the second one only matches strings the first one
+    /// matches too, so it never wins
+    Subsumed,
+    /// both match this string, and the first one wins
+    /// because it's declared first
+    Tie(String),
+    /// one of them matches this string, which is how the other
+    /// one starts, so which one wins depends on what comes next
+    Prefix { shorter: usize, prefix: String },
+}
+
+/// Finds the pairs of alternatives that can match the same input.
+/// Alternatives made of literals and of rules that only match a
+/// few strings are compared by the strings they match, the others
+/// only when they're written the same way
+pub fn ambiguities<'a>(gram: &Grammar<'a>) -> Vec<Ambiguity<'a>> {
+    let mut ids: Vec<_> = gram.rules.keys().copied().collect();
+    ids.sort_unstable();
+
+    let mut found = Vec::new();
+    for id in ids {
+        let rule = &gram.rules[&id];
+        let alternatives = &rule.rule.0;
+
+        let strings: Vec<_> = alternatives
+            .iter()
+            .map(|list| list_strings(gram, list, &mut FxHashSet::default()))
+            .collect();
+
+        for first in 0..alternatives.len() {
+            for second in first + 1..alternatives.len() {
+                let kind = compare(
+                    gram,
+                    (first, &alternatives[first], &strings[first]),
+                    (second, &alternatives[second], &strings[second]),
+                );
+
+                if let Some(kind) = kind {
+                    found.push(Ambiguity {
+                        rule: rule.name,
+                        first: first + 1,
+                        second: second + 1,
+                        kind,
+                        strategy: gram.choice(rule.name),
+                    });
+                }
+            }
+        }
+    }
+
+    found
+}
+
+impl<'a> Ambiguity<'a> {
+    /// Whether the order of the alternatives decides which one
+    /// wins, instead of the length of what they match
+    pub fn decided_by_order(&self) -> bool {
+        self.strategy == ChoiceStrategy::First || !matches!(self.kind, AmbiguityKind::Prefix { .. })
+    }
+}
+
+type Alternative<'r, 'a> = (usize, &'r RuleList<'a>, &'r Option<BTreeSet<String>>);
+
+fn compare(gram: &Grammar, first: Alternative, second: Alternative) -> Option<AmbiguityKind> {
+    let (_, first_list, first_strings) = first;
+    let (_, second_list, second_strings) = second;
+
+    if first_list == second_list {
+        return Some(AmbiguityKind::Identical);
+    }
+
+    if let (Some(first_strings), Some(second_strings)) = (first_strings, second_strings) {
+        if first_strings == second_strings {
+            return Some(AmbiguityKind::Identical);
+        }
+        if second_strings.is_subset(first_strings) {
+            return Some(AmbiguityKind::Subsumed);
+        }
+        if let Some(tie) = first_strings.intersection(second_strings).next() {
+            return Some(AmbiguityKind::Tie(tie.clone()));
+        }
+    }
+
+    prefix(gram, first, second)
+        .or_else(|| prefix(gram, second, first))
+        .map(|(shorter, prefix)| AmbiguityKind::Prefix {
+            shorter: shorter + 1,
+            prefix,
+        })
+}
+
+/// Checks if a string matched by `shorter` is how `longer` starts
+fn prefix(gram: &Grammar, shorter: Alternative, longer: Alternative) -> Option<(usize, String)> {
+    let (index, _, strings) = shorter;
+    let strings = strings.as_ref()?;
+
+    let (_, longer_list, longer_strings) = longer;
+    let starts: Vec<(String, bool)> = match longer_strings {
+        Some(longer_strings) => longer_strings.iter().map(|s| (s.clone(), true)).collect(),
+        None => vec![leading_text(gram, longer_list)],
+    };
+
+    strings
+        .iter()
+        .filter(|s| !s.is_empty())
+        .find(|s| {
+            starts.iter().any(|(start, complete)| {
+                start.starts_with(s.as_str()) && (start.len() > s.len() || !complete)
+            })
+        })
+        .map(|s| (index, s.clone()))
+}
+
+/// The text every match of `list` starts with, and whether
+/// that's all of it
+fn leading_text(gram: &Grammar, list: &RuleList) -> (String, bool) {
+    let mut text = String::new();
+
+    for piece in &list.0 {
+        let strings = piece_strings(gram, piece, &mut FxHashSet::default());
+        match strings {
+            Some(strings) if strings.len() == 1 => text.push_str(strings.iter().next().unwrap()),
+            _ => return (text, false),
+        }
+    }
+
+    (text, true)
+}
+
+/// Every string `list` can match, if there aren't too many
+fn list_strings(
+    gram: &Grammar,
+    list: &RuleList,
+    visiting: &mut FxHashSet<RuleRef>,
+) -> Option<BTreeSet<String>> {
+    let mut strings = BTreeSet::from([String::new()]);
+
+    for piece in &list.0 {
+        let next = piece_strings(gram, piece, visiting)?;
+
+        let mut joined = BTreeSet::new();
+        for start in &strings {
+            for end in &next {
+                joined.insert(format!("{}{}", start, end));
+            }
+        }
+
+        if joined.len() > MAX_STRINGS {
+            return None;
+        }
+        strings = joined;
+    }
+
+    Some(strings)
+}
+
+fn piece_strings(
+    gram: &Grammar,
+    piece: &RulePiece,
+    visiting: &mut FxHashSet<RuleRef>,
+) -> Option<BTreeSet<String>> {
+    // a repetition matches infinite strings
+    if piece.repetition != Repetition::Single {
+        return None;
+    }
+
+    match &piece.content {
+        RulePieceContent::Literal(literal) => match literal.content {
+            LiteralContent::Str(string) => Some(BTreeSet::from([string.to_string()])),
+            LiteralContent::Range { from, to } => {
+                if (to as u32).saturating_sub(from as u32) >= MAX_RANGE {
+                    return None;
+                }
+
+                Some((from..=to).map(String::from).collect())
+            }
+        },
+        RulePieceContent::Rule(rf) => {
+            // a recursive rule matches infinite strings
+            if !visiting.insert(*rf) {
+                return None;
+            }
+
+            let mut strings = BTreeSet::new();
+            for list in &gram.rules.get(rf)?.rule.0 {
+                strings.extend(list_strings(gram, list, visiting)?);
+
+                if strings.len() > MAX_STRINGS {
+                    return None;
+                }
+            }
+
+            visiting.remove(rf);
+            Some(strings)
+        }
+    }
+}
+
+impl<'a> fmt::Display for Ambiguity<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (rule, first, second) = (self.rule, self.first, self.second);
+        let tie = match self.strategy {
+            ChoiceStrategy::First => "the first one that matches wins",
+            ChoiceStrategy::Longest => "the first one wins because it's declared first",
+            ChoiceStrategy::Unambiguous => "parsing it is an error",
+        };
+
+        match &self.kind {
+            AmbiguityKind::Identical if self.strategy == ChoiceStrategy::Unambiguous => write!(
+                f,
+                "alternatives {} and {} of <{}> match the same strings, parsing any of them is an error",
+                first, second, rule
+            ),
+            AmbiguityKind::Identical => write!(
+                f,
+                "alternatives {} and {} of <{}> match the same strings, the first one always wins",
+                first, second, rule
+            ),
+            AmbiguityKind::Subsumed if self.strategy == ChoiceStrategy::Unambiguous => write!(
+                f,
+                "alternative {} of <{}> never matches alone, alternative {} matches everything it does and parsing those is an error",
+                second, rule, first
+            ),
+            AmbiguityKind::Subsumed => write!(
+                f,
+                "alternative {} of <{}> never matches, alternative {} matches everything it does",
+                second, rule, first
+            ),
+            AmbiguityKind::Tie(string) => write!(
+                f,
+                "alternatives {} and {} of <{}> both match '{}', {}",
+                first, second, rule, string, tie
+            ),
+            AmbiguityKind::Prefix { shorter, prefix } => {
+                let longer = if *shorter == first { second } else { first };
+                let wins = match self.strategy {
+                    ChoiceStrategy::First => "the first one that matches wins",
+                    _ => "the longest match wins",
+                };
+
+                write!(
+                    f,
+                    "alternative {} of <{}> matches '{}', which is how alternative {} starts, {}",
+                    shorter, rule, prefix, longer, wins
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node;
+
+    #[test]
+    fn ambiguities() {
+        let input = r#"
+<frase>: <eroe> | <saluto> | <cifra>;
+<eroe>: 'batman' | 'batman' <esclamazione> | 'robin' | 'robin';
+<esclamazione>: '!'+;
+<saluto>: 'ciao' | <ciao> | <ciao> 'ne';
+<ciao>: 'ciao' | 'hey';
+<cifra>: [0-9] | '7' | '7' | [0-8];"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let got: Vec<_> = super::ambiguities(&grammar)
+            .iter()
+            .map(|found| found.to_string())
+            .collect();
+
+        let expected = vec![
+            "alternative 1 of <eroe> matches 'batman', which is how alternative 2 starts, the longest match wins",
+            "alternatives 3 and 4 of <eroe> match the same strings, the first one always wins",
+            "alternatives 1 and 2 of <saluto> both match 'ciao', the first one wins because it's declared first",
+            "alternative 1 of <saluto> matches 'ciao', which is how alternative 3 starts, the longest match wins",
+            "alternative 2 of <saluto> matches 'ciao', which is how alternative 3 starts, the longest match wins",
+            "alternative 2 of <cifra> never matches, alternative 1 matches everything it does",
+            "alternative 3 of <cifra> never matches, alternative 1 matches everything it does",
+            "alternative 4 of <cifra> never matches, alternative 1 matches everything it does",
+            "alternatives 2 and 3 of <cifra> match the same strings, the first one always wins",
+            "alternatives 2 and 4 of <cifra> both match '7', the first one wins because it's declared first",
+            "alternatives 3 and 4 of <cifra> both match '7', the first one wins because it's declared first",
+        ];
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn strategies() {
+        let input = r#"
+@choice first <saluto>;
+@choice unambiguous <cifra>;
+<frase>: <saluto> | <cifra>;
+<saluto>: 'ciao' | <ciao>;
+<ciao>: 'ciao' | 'hey';
+<cifra>: '7' | [0-9] | '7';"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let got: Vec<_> = super::ambiguities(&grammar)
+            .iter()
+            .map(|found| found.to_string())
+            .collect();
+
+        let expected = vec![
+            "alternatives 1 and 2 of <saluto> both match 'ciao', the first one that matches wins",
+            "alternatives 1 and 2 of <cifra> both match '7', parsing it is an error",
+            "alternatives 1 and 3 of <cifra> match the same strings, parsing any of them is an error",
+            "alternative 3 of <cifra> never matches alone, alternative 2 matches everything it does and parsing those is an error",
+        ];
+        assert_eq!(got, expected);
+    }
+}
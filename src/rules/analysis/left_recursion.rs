@@ -0,0 +1,190 @@
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use super::nullable::is_nullable_piece;
+use super::nullable::nullable_rules;
+use crate::rules::grammar::Grammar;
+use crate::rules::rule::RulePiece;
+use crate::rules::rule::RulePieceContent;
+use crate::rules::rule::RuleRef;
+
+/// Finds every cycle of rules that can call each other without
+/// consuming any input, like `<expr>: <expr> '+' <term>;`. Each
+/// cycle starts and ends with the same rule, and starts from the
+/// rule with the lowest id, so every cycle is only reported once
+pub fn left_recursive_cycles(gram: &Grammar) -> Vec<Vec<RuleRef>> {
+    let edges = left_calls(gram);
+
+    let mut ids: Vec<_> = gram.rules.keys().copied().collect();
+    ids.sort_unstable();
+
+    let mut cycles = Vec::new();
+    let mut seen = FxHashSet::default();
+
+    for id in ids {
+        let mut path = vec![id];
+        let mut visiting = FxHashSet::default();
+        find_cycles(id, &edges, &mut path, &mut visiting, &mut seen, &mut cycles);
+    }
+
+    cycles
+}
+
+/// The rules each rule can call before consuming any input
+pub fn left_calls(gram: &Grammar) -> FxHashMap<RuleRef, Vec<RuleRef>> {
+    let nullable = nullable_rules(gram);
+    let mut edges = FxHashMap::default();
+
+    for (id, rule) in &gram.rules {
+        let mut called = Vec::new();
+
+        for list in &rule.rule.0 {
+            left_calls_in(&list.0, &nullable, &mut called);
+        }
+
+        // prefix operators are tried before the operands
+        if let Some(table) = gram.operators.get(rule.name) {
+            for op in table.prefix() {
+                if let RulePieceContent::Rule(rf) = op.content {
+                    called.push(rf);
+                }
+            }
+        }
+
+        called.sort_unstable();
+        called.dedup();
+        edges.insert(*id, called);
+    }
+
+    edges
+}
+
+fn left_calls_in(pieces: &[RulePiece], nullable: &FxHashSet<RuleRef>, called: &mut Vec<RuleRef>) {
+    for piece in pieces {
+        if let RulePieceContent::Rule(rf) = piece.content {
+            called.push(rf);
+        }
+
+        if !is_nullable_piece(piece, nullable) {
+            break;
+        }
+    }
+}
+
+fn find_cycles(
+    start: RuleRef,
+    edges: &FxHashMap<RuleRef, Vec<RuleRef>>,
+    path: &mut Vec<RuleRef>,
+    visiting: &mut FxHashSet<RuleRef>,
+    seen: &mut FxHashSet<Vec<RuleRef>>,
+    cycles: &mut Vec<Vec<RuleRef>>,
+) {
+    let current = *path.last().unwrap();
+    let called = match edges.get(&current) {
+        Some(called) => called,
+        None => return,
+    };
+
+    for next in called {
+        if *next == start {
+            let mut cycle = path.clone();
+            cycle.push(start);
+
+            if seen.insert(canonical(&cycle)) {
+                cycles.push(cycle);
+            }
+        } else if *next > start && visiting.insert(*next) {
+            // rules with a lower id already had all their cycles found
+            path.push(*next);
+            find_cycles(start, edges, path, visiting, seen, cycles);
+            path.pop();
+            // other paths can go through it too
+            visiting.remove(next);
+        }
+    }
+}
+
+/// The same cycle can be found starting from any of its rules
+fn canonical(cycle: &[RuleRef]) -> Vec<RuleRef> {
+    let rules = &cycle[..cycle.len() - 1];
+    let min = (0..rules.len()).min_by_key(|i| rules[*i]).unwrap();
+
+    let mut rotated = rules[min..].to_vec();
+    rotated.extend_from_slice(&rules[..min]);
+    rotated
+}
+
+/// Writes a cycle like `<expr> -> <term> -> <expr>`
+pub fn cycle_to_string(gram: &Grammar, cycle: &[RuleRef]) -> String {
+    let names: Vec<_> = cycle
+        .iter()
+        .map(|id| format!("<{}>", gram.get(*id).unwrap().name))
+        .collect();
+
+    names.join(" -> ")
+}
+
+/// A warning for every left recursive cycle. They can be parsed,
+/// but it's slower, and easy to write by mistake
+pub fn left_recursion_warnings(gram: &Grammar) -> Vec<String> {
+    left_recursive_cycles(gram)
+        .iter()
+        .map(|cycle| format!("left recursion: {}", cycle_to_string(gram, cycle)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node;
+
+    #[test]
+    fn cycles() {
+        let cases = [
+            (
+                "<expr>: <expr> '+' <term> | <term>; <term>: [0-9];",
+                vec!["<expr> -> <expr>"],
+            ),
+            (
+                "<a>: <b> 'x'; <b>: <c> | 'y'; <c>: 'z' | <a>;",
+                vec!["<a> -> <b> -> <c> -> <a>"],
+            ),
+            (
+                "<a>: <vuoto> <a> 'x' | 'y'; <vuoto>: '' | ' ';",
+                vec!["<a> -> <a>"],
+            ),
+            ("<a>: 'x' <a> | 'y';", vec![]),
+            (
+                "<a>: <b> 'x' | <c> 'y' | 'z'; <b>: <d>; <c>: <d>; <d>: <a> '-';",
+                vec!["<a> -> <b> -> <d> -> <a>", "<a> -> <c> -> <d> -> <a>"],
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+            let grammar = Grammar::try_from(&spec).unwrap();
+
+            let got: Vec<_> = left_recursive_cycles(&grammar)
+                .iter()
+                .map(|cycle| cycle_to_string(&grammar, cycle))
+                .collect();
+
+            assert_eq!(got, expected);
+            // every rule in a cycle gets parsed as left recursive
+            for cycle in left_recursive_cycles(&grammar) {
+                for id in cycle {
+                    assert!(grammar.is_left_recursive(grammar.get(id).unwrap().name));
+                }
+            }
+        }
+
+        let input = "<a>: <b> 'x'; <b>: <c> | 'y'; <c>: 'z' | <a>;";
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        assert_eq!(
+            left_recursion_warnings(&grammar),
+            vec!["left recursion: <a> -> <b> -> <c> -> <a>"]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod ambiguity;
+pub mod left_recursion;
+pub mod nullable;
+pub mod productive;
+pub mod reachability;
+pub mod sets;
@@ -0,0 +1,118 @@
+use rustc_hash::FxHashSet;
+
+use crate::rules::grammar::Grammar;
+use crate::rules::literal::LiteralContent;
+use crate::rules::rule::RuleList;
+use crate::rules::rule::RulePiece;
+use crate::rules::rule::RulePieceContent;
+use crate::rules::rule::RuleRef;
+use crate::spec_parser::rule_piece::Repetition;
+
+/// Finds the rules that can match the empty string. Repeated
+/// pieces need to match at least once, so they're nullable
+/// only if what they repeat is
+pub fn nullable_rules(gram: &Grammar) -> FxHashSet<RuleRef> {
+    let mut nullable = FxHashSet::default();
+
+    // keep going until nothing changes: a rule can become
+    // nullable because of a rule we've already checked
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (id, rule) in &gram.rules {
+            if nullable.contains(id) {
+                continue;
+            }
+
+            if rule
+                .rule
+                .0
+                .iter()
+                .any(|list| is_nullable_list(list, &nullable))
+            {
+                nullable.insert(*id);
+                changed = true;
+            }
+        }
+    }
+
+    nullable
+}
+
+/// A repeated piece that can match the empty string, found
+/// in one of the alternatives of `rule`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NullableRepetition {
+    pub rule: RuleRef,
+    pub alternative: usize,
+    pub piece: usize,
+}
+
+/// Finds the pieces that are repeated with `+` or `*`, but can
+/// match nothing, like `<spazio>*` with `<spazio>: ' '+ | '';`
+pub fn nullable_repetitions(gram: &Grammar) -> Vec<NullableRepetition> {
+    let nullable = nullable_rules(gram);
+    let mut found = Vec::new();
+
+    for (id, rule) in &gram.rules {
+        for (alternative, list) in rule.rule.0.iter().enumerate() {
+            for (i, piece) in list.0.iter().enumerate() {
+                if piece.repetition != Repetition::Single && is_nullable_piece(piece, &nullable) {
+                    found.push(NullableRepetition {
+                        rule: *id,
+                        alternative,
+                        piece: i,
+                    });
+                }
+            }
+        }
+    }
+
+    found.sort_unstable_by_key(|rep| (rep.rule, rep.alternative, rep.piece));
+    found
+}
+
+pub fn is_nullable_list(list: &RuleList, nullable: &FxHashSet<RuleRef>) -> bool {
+    list.0
+        .iter()
+        .all(|piece| is_nullable_piece(piece, nullable))
+}
+
+pub fn is_nullable_piece(piece: &RulePiece, nullable: &FxHashSet<RuleRef>) -> bool {
+    match &piece.content {
+        RulePieceContent::Literal(literal) => match literal.content {
+            LiteralContent::Str(string) => string.is_empty(),
+            LiteralContent::Range { .. } => false,
+        },
+        RulePieceContent::Rule(rf) => nullable.contains(rf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node;
+
+    #[test]
+    fn nullable() {
+        let input = r#"
+<oggetto>: '{' <spazi> '}';
+<spazi>: <spazio> | <spazio> ',' <spazi>;
+<spazio>: ' '+ | <niente>;
+<niente>: '';"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+
+        let mut got: Vec<_> = nullable_rules(&grammar)
+            .into_iter()
+            .map(|id| grammar.get(id).unwrap().name)
+            .collect();
+        got.sort();
+
+        assert_eq!(got, vec!["niente", "spazi", "spazio"]);
+    }
+}
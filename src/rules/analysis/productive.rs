@@ -0,0 +1,168 @@
+use rustc_hash::FxHashSet;
+
+use crate::rules::grammar::Grammar;
+use crate::rules::rule::RuleList;
+use crate::rules::rule::RulePieceContent;
+use crate::rules::rule::RuleRef;
+
+/// Finds the rules that can match some finite input. A rule is
+/// productive when one of its alternatives only uses literals
+/// and productive rules, since every piece matches at least once
+pub fn productive_rules(gram: &Grammar) -> FxHashSet<RuleRef> {
+    let mut productive = FxHashSet::default();
+
+    // same as with nullable rules, a rule can become
+    // productive because of a rule we've already checked
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (id, rule) in &gram.rules {
+            if productive.contains(id) {
+                continue;
+            }
+
+            if rule
+                .rule
+                .0
+                .iter()
+                .any(|list| is_productive_list(list, &productive))
+            {
+                productive.insert(*id);
+                changed = true;
+            }
+        }
+    }
+
+    productive
+}
+
+/// A rule that can't match anything, because every alternative
+/// needs another rule that can't either
+#[derive(Debug, PartialEq, Clone)]
+pub struct NonproductiveRule {
+    pub rule: RuleRef,
+    /// the rules it depends on, until one repeats,
+    /// like `<a> -> <b> -> <a>`
+    pub chain: Vec<RuleRef>,
+}
+
+/// Finds every rule that can't match any finite input, sorted by id
+pub fn nonproductive_rules(gram: &Grammar) -> Vec<NonproductiveRule> {
+    let productive = productive_rules(gram);
+
+    let mut ids: Vec<_> = gram
+        .rules
+        .keys()
+        .filter(|id| !productive.contains(id))
+        .copied()
+        .collect();
+    ids.sort_unstable();
+
+    ids.into_iter()
+        .map(|rule| NonproductiveRule {
+            rule,
+            chain: chain(gram, rule, &productive),
+        })
+        .collect()
+}
+
+/// Follows the first rule that isn't productive in the first
+/// alternative, until we get back to a rule we've already seen
+fn chain(gram: &Grammar, start: RuleRef, productive: &FxHashSet<RuleRef>) -> Vec<RuleRef> {
+    let mut chain = vec![start];
+    let mut seen = FxHashSet::default();
+    seen.insert(start);
+
+    let mut current = start;
+    loop {
+        // every alternative has one, or the rule would be productive
+        let next = gram.rules[&current].rule.0.iter().find_map(|list| {
+            list.0.iter().find_map(|piece| match piece.content {
+                RulePieceContent::Rule(rf) if !productive.contains(&rf) => Some(rf),
+                _ => None,
+            })
+        });
+
+        let next = match next {
+            Some(next) => next,
+            // a rule without alternatives
+            None => return chain,
+        };
+
+        chain.push(next);
+        if !seen.insert(next) {
+            return chain;
+        }
+
+        current = next;
+    }
+}
+
+fn is_productive_list(list: &RuleList, productive: &FxHashSet<RuleRef>) -> bool {
+    list.0.iter().all(|piece| match &piece.content {
+        RulePieceContent::Literal(_) => true,
+        RulePieceContent::Rule(rf) => productive.contains(rf),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::analysis::left_recursion::cycle_to_string;
+    use crate::rules::rule::Rule;
+    use crate::rules::rule::RuleOrs;
+    use rustc_hash::FxHashMap;
+
+    #[test]
+    fn chains() {
+        // <inizio>: <a> | <c>; <a>: <b>; <b>: <a> 'x'; <c>: 'c';
+        let rule = |name, lists: Vec<Vec<RulePieceContent<'static>>>| Rule {
+            name,
+            rule: RuleOrs(
+                lists
+                    .into_iter()
+                    .map(|list| RuleList(list.into_iter().map(|content| content.into()).collect()))
+                    .collect(),
+            ),
+        };
+
+        let mut rules = FxHashMap::default();
+        rules.insert(
+            0,
+            rule(
+                "inizio",
+                vec![
+                    vec![RulePieceContent::Rule(1)],
+                    vec![RulePieceContent::Rule(3)],
+                ],
+            ),
+        );
+        rules.insert(1, rule("a", vec![vec![RulePieceContent::Rule(2)]]));
+        rules.insert(
+            2,
+            rule(
+                "b",
+                vec![vec![
+                    RulePieceContent::Rule(1),
+                    RulePieceContent::Literal("x".into()),
+                ]],
+            ),
+        );
+        rules.insert(
+            3,
+            rule("c", vec![vec![RulePieceContent::Literal("c".into())]]),
+        );
+        let grammar = Grammar {
+            rules,
+            ..Default::default()
+        };
+
+        let got: Vec<_> = nonproductive_rules(&grammar)
+            .iter()
+            .map(|rule| cycle_to_string(&grammar, &rule.chain))
+            .collect();
+
+        assert_eq!(got, vec!["<a> -> <b> -> <a>", "<b> -> <a> -> <b>"]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::fmt;
+
+use rustc_hash::FxHashSet;
+
+use crate::spec_parser::content::Content;
+use crate::spec_parser::directive::DirectiveArg;
+use crate::spec_parser::grammar::Grammar as SpecGrammar;
+use crate::spec_parser::rule_piece::RulePiece;
+use crate::spec_parser::rule_piece::RulePieceContent;
+use crate::utils::edit_distance;
+use crate::utils::Position;
+
+/// Problems with how the rules of a grammar refer to each other,
+/// all found in one go. Every list is sorted by rule name
+#[derive(Debug, PartialEq, Default)]
+pub struct LintReport<'a> {
+    /// rules that are never referenced by any other rule
+    pub unused: Vec<&'a str>,
+    /// rules that are referenced, but only by rules that
+    /// can't be reached from the main one
+    pub unreachable: Vec<&'a str>,
+    pub undefined: Vec<UndefinedRule<'a>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UndefinedRule<'a> {
+    pub name: &'a str,
+    pub referenced_by: Referrer<'a>,
+    /// where the reference is in the grammar's source
+    pub position: Position,
+    /// the defined rules with the most similar names
+    pub suggestions: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Referrer<'a> {
+    Rule(&'a str),
+    Directive(&'a str),
+}
+
+impl<'a> LintReport<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.unused.is_empty() && self.unreachable.is_empty() && self.undefined.is_empty()
+    }
+
+    /// Whether the grammar can't be compiled
+    pub fn has_errors(&self) -> bool {
+        !self.undefined.is_empty()
+    }
+}
+
+/// Checks which rules are used, and which ones are referenced
+/// without being defined. The main rule and the skip rules are
+/// used even if nothing refers to them
+pub fn lint<'a>(gram: &SpecGrammar<'a>) -> LintReport<'a> {
+    let mut report = LintReport::default();
+    let mut referenced = FxHashSet::default();
+
+    let mut roots = vec![gram.main];
+
+    for directive in &gram.directives {
+        for arg in &directive.args {
+            let (name, piece) = match arg {
+                DirectiveArg::Piece(piece) => match ident(piece) {
+                    Some(name) => (name, piece),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            if !gram.rules.contains_key(name) {
+                report.undefined.push(UndefinedRule::new(
+                    gram,
+                    piece,
+                    Referrer::Directive(directive.name),
+                ));
+            }
+
+            match directive.name {
+                "skip" => roots.push(name),
+                // like the rule the operators belong to and the operators,
+                // or the rules made atomic
+                _ => {
+                    referenced.insert(name);
+                }
+            }
+        }
+    }
+
+    let mut names: Vec<_> = gram.rules.keys().copied().collect();
+    names.sort_unstable();
+
+    for name in &names {
+        for (called, piece) in calls(gram, name) {
+            referenced.insert(called);
+
+            if !gram.rules.contains_key(called) {
+                report
+                    .undefined
+                    .push(UndefinedRule::new(gram, piece, Referrer::Rule(name)));
+            }
+        }
+    }
+
+    let mut reachable = FxHashSet::default();
+    let mut to_visit = roots.clone();
+
+    while let Some(name) = to_visit.pop() {
+        if !reachable.insert(name) {
+            continue;
+        }
+
+        to_visit.extend(calls(gram, name).map(|(called, _)| called));
+        to_visit.extend(operator_calls(gram, name));
+    }
+
+    for name in names {
+        if roots.contains(&name) {
+            continue;
+        } else if !referenced.contains(name) {
+            report.unused.push(name);
+        } else if !reachable.contains(name) {
+            report.unreachable.push(name);
+        }
+    }
+
+    report.undefined.sort_by_key(|undefined| undefined.position);
+    report
+}
+
+/// How many suggestions to give for an undefined rule
+const MAX_SUGGESTIONS: usize = 3;
+
+impl<'a> UndefinedRule<'a> {
+    /// `piece` is the reference to the undefined rule
+    fn new(gram: &SpecGrammar<'a>, piece: &RulePiece<'a>, referenced_by: Referrer<'a>) -> Self {
+        let (name, position) = match &piece.content {
+            RulePieceContent::Ident(ident) => {
+                (ident.content(), Position::of_slice(gram.source, ident.0))
+            }
+            _ => unreachable!(),
+        };
+
+        Self {
+            name,
+            referenced_by,
+            position,
+            suggestions: suggestions(gram, name),
+        }
+    }
+}
+
+/// The names of the rules that are close enough to `name` to
+/// probably be what was meant, the closest first
+fn suggestions<'a>(gram: &SpecGrammar<'a>, name: &str) -> Vec<&'a str> {
+    // a third of the name can be wrong, but at least one character,
+    // as long as something is left of the name
+    let len = name.chars().count();
+    let max_distance = (len / 3).max(1);
+
+    let mut close: Vec<_> = gram
+        .rules
+        .keys()
+        .map(|rule| (edit_distance(name, rule), *rule))
+        .filter(|(distance, _)| *distance <= max_distance && *distance < len)
+        .collect();
+    close.sort_unstable();
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, rule)| rule)
+        .collect()
+}
+
+/// The rules referenced in the body of a rule, with the
+/// piece that references them
+pub fn calls<'a, 'b>(
+    gram: &'b SpecGrammar<'a>,
+    name: &str,
+) -> impl Iterator<Item = (&'a str, &'b RulePiece<'a>)> {
+    gram.rules
+        .get(name)
+        .into_iter()
+        .flat_map(|line| line.rules.0.iter())
+        .flat_map(|series| series.0.iter())
+        .filter_map(|piece| ident(piece).map(|name| (name, piece)))
+}
+
+/// The rules used as operators by a rule declared with `@operators`
+fn operator_calls<'a>(gram: &SpecGrammar<'a>, name: &str) -> Vec<&'a str> {
+    gram.directives
+        .iter()
+        .filter(|directive| directive.name == "operators")
+        .filter(|directive| match directive.args.first() {
+            Some(DirectiveArg::Piece(piece)) => ident(piece) == Some(name),
+            _ => false,
+        })
+        .flat_map(|directive| directive.args.iter().skip(1))
+        .filter_map(|arg| match arg {
+            DirectiveArg::Piece(piece) => ident(piece),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ident<'a>(piece: &RulePiece<'a>) -> Option<&'a str> {
+    match &piece.content {
+        RulePieceContent::Ident(ident) => Some(ident.content()),
+        _ => None,
+    }
+}
+
+impl<'a> fmt::Display for Referrer<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Referrer::Rule(name) => write!(f, "<{}>", name),
+            Referrer::Directive(name) => write!(f, "@{}", name),
+        }
+    }
+}
+
+impl<'a> fmt::Display for UndefinedRule<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can't find rule <{}>, used in {} at {}",
+            self.name, self.referenced_by, self.position
+        )?;
+
+        if !self.suggestions.is_empty() {
+            let names: Vec<_> = self
+                .suggestions
+                .iter()
+                .map(|name| format!("<{}>", name))
+                .collect();
+
+            write!(f, ", did you mean {}?", names.join(" or "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for LintReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+
+        for undefined in &self.undefined {
+            lines.push(format!("error: {}", undefined));
+        }
+
+        for name in &self.unused {
+            lines.push(format!("warning: rule <{}> is never used", name));
+        }
+
+        for name in &self.unreachable {
+            lines.push(format!(
+                "warning: rule <{}> can't be reached from the main rule",
+                name
+            ));
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec_parser::node::Node;
+
+    #[test]
+    fn report() {
+        let input = r#"
+<frase>: <soggetto> ' ' <verbo>;
+<soggetto>: 'io' | <pronome>;
+<pronomi>: 'tu';
+<verbo>: 'mangio' | <verbi_irregolari>;
+<inutile>: <anche_inutile>;
+<anche_inutile>: 'x';
+<solo>: 'y';
+@skip <spazio>;
+<spazio>: <blank>;
+@atomic <atomo>;
+<atomo>: 'z';"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let report = lint(&spec);
+
+        assert_eq!(report.unused, vec!["inutile", "pronomi", "solo"]);
+        assert_eq!(report.unreachable, vec!["anche_inutile", "atomo"]);
+
+        let undefined: Vec<_> = report.undefined.iter().map(|u| u.to_string()).collect();
+        assert_eq!(
+            undefined,
+            vec![
+                "can't find rule <pronome>, used in <soggetto> at line 2, column 20, did you mean <pronomi>?",
+                "can't find rule <verbi_irregolari>, used in <verbo> at line 4, column 21",
+                "can't find rule <blank>, used in <spazio> at line 9, column 11",
+            ]
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use super::nullable::is_nullable_piece;
+use super::nullable::nullable_rules;
+use crate::rules::grammar::Grammar;
+use crate::rules::literal::LiteralContent;
+use crate::rules::rule::RulePiece;
+use crate::rules::rule::RulePieceContent;
+use crate::rules::rule::RuleRef;
+use crate::spec_parser::rule_piece::Repetition;
+
+/// A set of characters, kept as sorted intervals
+/// that don't overlap and don't touch
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct CharSet(pub Vec<(char, char)>);
+
+/// The terminals that can come at some point of the input
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TerminalSet<'a> {
+    /// string literals, as they are written in the grammar
+    pub literals: BTreeSet<&'a str>,
+    /// char ranges
+    pub ranges: CharSet,
+    /// whether the input can end here
+    pub end: bool,
+}
+
+/// What can be matched at the beginning and after each rule.
+/// Skip rules are not taken into account
+#[derive(Debug, PartialEq, Default)]
+pub struct GrammarSets<'a> {
+    /// the rules that can match the empty string
+    pub nullable: FxHashSet<RuleRef>,
+    /// what each rule can begin with
+    pub first: FxHashMap<RuleRef, TerminalSet<'a>>,
+    /// what can come after each rule
+    pub follow: FxHashMap<RuleRef, TerminalSet<'a>>,
+}
+
+impl CharSet {
+    pub fn insert(&mut self, from: char, to: char) {
+        let (mut from, mut to) = (from, to);
+        let mut merged = Vec::with_capacity(self.0.len() + 1);
+
+        for (start, end) in self.0.drain(..) {
+            // touching intervals are merged too
+            if (end as u32) + 1 < from as u32 || (to as u32) + 1 < start as u32 {
+                merged.push((start, end));
+            } else {
+                from = from.min(start);
+                to = to.max(end);
+            }
+        }
+
+        merged.push((from, to));
+        merged.sort_unstable();
+        self.0 = merged;
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.0.iter().any(|(from, to)| *from <= c && c <= *to)
+    }
+
+    pub fn extend(&mut self, other: &CharSet) {
+        for (from, to) in &other.0 {
+            self.insert(*from, *to);
+        }
+    }
+}
+
+impl<'a> TerminalSet<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.literals.is_empty() && self.ranges.0.is_empty() && !self.end
+    }
+
+    /// Adds everything in `other`, and returns whether
+    /// something new was added
+    pub fn extend(&mut self, other: &TerminalSet<'a>) -> bool {
+        let before = (self.literals.len(), self.ranges.clone(), self.end);
+
+        self.literals.extend(other.literals.iter().copied());
+        self.ranges.extend(&other.ranges);
+        self.end |= other.end;
+
+        before != (self.literals.len(), self.ranges.clone(), self.end)
+    }
+
+    /// Every character the terminals can begin with
+    pub fn first_chars(&self) -> CharSet {
+        let mut chars = self.ranges.clone();
+
+        for literal in &self.literals {
+            if let Some(c) = literal.chars().next() {
+                chars.insert(c, c);
+            }
+        }
+
+        chars
+    }
+}
+
+pub fn grammar_sets<'a>(gram: &Grammar<'a>) -> GrammarSets<'a> {
+    let nullable = nullable_rules(gram);
+    let first = first_sets(gram, &nullable);
+    let follow = follow_sets(gram, &nullable, &first);
+
+    GrammarSets {
+        nullable,
+        first,
+        follow,
+    }
+}
+
+fn first_sets<'a>(
+    gram: &Grammar<'a>,
+    nullable: &FxHashSet<RuleRef>,
+) -> FxHashMap<RuleRef, TerminalSet<'a>> {
+    let mut first: FxHashMap<RuleRef, TerminalSet<'a>> = gram
+        .rules
+        .keys()
+        .map(|id| (*id, TerminalSet::default()))
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (id, rule) in &gram.rules {
+            let mut found = TerminalSet::default();
+
+            for list in &rule.rule.0 {
+                found.extend(&first_of_pieces(&list.0, nullable, &first));
+            }
+
+            // prefix operators come before the operands
+            if let Some(table) = gram.operators.get(rule.name) {
+                for op in table.prefix() {
+                    found.extend(&first_of_content(&op.content, &first));
+                }
+            }
+
+            changed |= first.get_mut(id).unwrap().extend(&found);
+        }
+    }
+
+    first
+}
+
+/// What a sequence of pieces can begin with. If every piece
+/// is nullable the sequence can be empty, but that's up to the
+/// caller to check
+pub fn first_of_pieces<'a>(
+    pieces: &[RulePiece<'a>],
+    nullable: &FxHashSet<RuleRef>,
+    first: &FxHashMap<RuleRef, TerminalSet<'a>>,
+) -> TerminalSet<'a> {
+    let mut found = TerminalSet::default();
+
+    for piece in pieces {
+        found.extend(&first_of_content(&piece.content, first));
+
+        if !is_nullable_piece(piece, nullable) {
+            break;
+        }
+    }
+
+    found
+}
+
+fn first_of_content<'a>(
+    content: &RulePieceContent<'a>,
+    first: &FxHashMap<RuleRef, TerminalSet<'a>>,
+) -> TerminalSet<'a> {
+    let mut found = TerminalSet::default();
+
+    match content {
+        RulePieceContent::Literal(literal) => match literal.content {
+            LiteralContent::Str("") => {}
+            LiteralContent::Str(string) => {
+                found.literals.insert(string);
+            }
+            LiteralContent::Range { from, to } => found.ranges.insert(from, to),
+        },
+        RulePieceContent::Rule(rf) => {
+            if let Some(set) = first.get(rf) {
+                found.extend(set);
+            }
+        }
+    }
+
+    found
+}
+
+fn follow_sets<'a>(
+    gram: &Grammar<'a>,
+    nullable: &FxHashSet<RuleRef>,
+    first: &FxHashMap<RuleRef, TerminalSet<'a>>,
+) -> FxHashMap<RuleRef, TerminalSet<'a>> {
+    let mut follow: FxHashMap<RuleRef, TerminalSet<'a>> = gram
+        .rules
+        .keys()
+        .map(|id| (*id, TerminalSet::default()))
+        .collect();
+
+    // the main rule is followed by the end of the input
+    if let Some(main) = follow.get_mut(&0) {
+        main.end = true;
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (id, rule) in &gram.rules {
+            // after an operand there can be another operator
+            let mut operators = TerminalSet::default();
+            if let Some(table) = gram.operators.get(rule.name) {
+                for op in table.infix() {
+                    operators.extend(&first_of_content(&op.content, first));
+                }
+            }
+
+            for list in &rule.rule.0 {
+                for (i, piece) in list.0.iter().enumerate() {
+                    let rf = match piece.content {
+                        RulePieceContent::Rule(rf) => rf,
+                        _ => continue,
+                    };
+
+                    let rest = &list.0[i + 1..];
+                    let mut found = first_of_pieces(rest, nullable, first);
+
+                    if rest.iter().all(|piece| is_nullable_piece(piece, nullable)) {
+                        found.extend(&follow[id]);
+                        found.extend(&operators);
+                    }
+
+                    // a repeated rule can be followed by itself
+                    if piece.repetition != Repetition::Single {
+                        found.extend(&first[&rf]);
+                    }
+
+                    changed |= follow.get_mut(&rf).unwrap().extend(&found);
+                }
+            }
+
+            // operators that are rules are followed by operands, or by
+            // whatever follows the whole rule for postfix operators
+            if let Some(table) = gram.operators.get(rule.name) {
+                for op in &table.0 {
+                    if let RulePieceContent::Rule(rf) = op.content {
+                        let mut found = first[id].clone();
+                        found.extend(&follow[id]);
+                        found.extend(&operators);
+
+                        changed |= follow.get_mut(&rf).unwrap().extend(&found);
+                    }
+                }
+            }
+        }
+    }
+
+    follow
+}
+
+impl fmt::Display for CharSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<_> = self
+            .0
+            .iter()
+            .map(|(from, to)| match from == to {
+                true => format!("[{}]", from),
+                false => format!("[{}-{}]", from, to),
+            })
+            .collect();
+
+        write!(f, "{}", ranges.join(" "))
+    }
+}
+
+impl<'a> fmt::Display for TerminalSet<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut terminals: Vec<_> = self
+            .literals
+            .iter()
+            .map(|literal| format!("'{}'", literal))
+            .collect();
+
+        if !self.ranges.0.is_empty() {
+            terminals.push(self.ranges.to_string());
+        }
+
+        if self.end {
+            terminals.push("end of input".into());
+        }
+
+        write!(f, "{}", terminals.join(" "))
+    }
+}
+
+impl<'a> GrammarSets<'a> {
+    /// Writes nullable, FIRST and FOLLOW for every rule,
+    /// in the order the rules got compiled
+    pub fn report(&self, gram: &Grammar<'a>) -> String {
+        let mut ids: Vec<_> = gram.rules.keys().copied().collect();
+        ids.sort_unstable();
+
+        let lines: Vec<_> = ids
+            .into_iter()
+            .map(|id| {
+                format!(
+                    "<{}>{}\n    first: {}\n    follow: {}",
+                    gram.rules[&id].name,
+                    match self.nullable.contains(&id) {
+                        true => " (nullable)",
+                        false => "",
+                    },
+                    self.first[&id],
+                    self.follow[&id],
+                )
+            })
+            .collect();
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec_parser::grammar::Grammar as SpecGrammar;
+    use crate::spec_parser::node::Node;
+
+    #[test]
+    fn char_set() {
+        let mut set = CharSet::default();
+        set.insert('a', 'f');
+        set.insert('0', '9');
+        set.insert('g', 'k');
+        set.insert('x', 'z');
+        set.insert('c', 'h');
+
+        assert_eq!(set, CharSet(vec![('0', '9'), ('a', 'k'), ('x', 'z')]));
+        assert!(set.contains('y'));
+        assert!(!set.contains('m'));
+    }
+
+    #[test]
+    fn sets() {
+        let input = r#"
+<oggetto>: '{' <spazio> <coppie> <spazio> '}';
+<coppie>: <coppia> | <coppia> ',' <coppie>;
+<coppia>: <chiave> <spazio> ':' <spazio> <valore>;
+<chiave>: [a-z]+;
+<valore>: <numero> | 'true' | 'false';
+<numero>: <cifra>+;
+<cifra>: [0-9];
+<spazio>: ' '+ | '';"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let grammar = Grammar::try_from(&spec).unwrap();
+        let sets = grammar_sets(&grammar);
+
+        let rule = |name: &str| {
+            *grammar
+                .rules
+                .iter()
+                .find(|(_, rule)| rule.name == name)
+                .unwrap()
+                .0
+        };
+
+        let cases = [
+            ("oggetto", false, "'{'", "end of input"),
+            ("coppie", false, "[a-z]", "' ' '}'"),
+            ("valore", false, "'false' 'true' [0-9]", "' ' ',' '}'"),
+            ("cifra", false, "[0-9]", "' ' ',' '}' [0-9]"),
+            ("spazio", true, "' '", "':' 'false' 'true' '}' [0-9] [a-z]"),
+        ];
+
+        for (name, nullable, first, follow) in cases {
+            let id = rule(name);
+            assert_eq!(sets.nullable.contains(&id), nullable, "{}", name);
+            assert_eq!(sets.first[&id].to_string(), first, "{}", name);
+            assert_eq!(sets.follow[&id].to_string(), follow, "{}", name);
+        }
+    }
+}
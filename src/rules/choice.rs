@@ -0,0 +1,24 @@
+/// How a rule picks one of its alternatives when more than one matches
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum ChoiceStrategy {
+    /// try every alternative and keep the one that matches the
+    /// most input, the first one declared if there's a tie
+    #[default]
+    Longest,
+    /// keep the first alternative that matches, like PEG's ordered choice
+    First,
+    /// like `Longest`, but two alternatives matching the same
+    /// amount of input are an error
+    Unambiguous,
+}
+
+impl ChoiceStrategy {
+    pub fn from_word(word: &str) -> Option<Self> {
+        match word {
+            "longest" => Some(Self::Longest),
+            "first" => Some(Self::First),
+            "unambiguous" => Some(Self::Unambiguous),
+            _ => None,
+        }
+    }
+}
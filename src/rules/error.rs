@@ -0,0 +1,63 @@
+use thiserror::Error;
+
+use super::analysis::reachability::UndefinedRule;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum GrammarError<'a> {
+    #[error("{}", list(.0))]
+    UndefinedRules(Vec<UndefinedRule<'a>>),
+    #[error("unknown directive @{0}")]
+    UnknownDirective(&'a str),
+    #[error("invalid @{directive}: {message}")]
+    InvalidDirective { directive: &'a str, message: String },
+    #[error("{}", list(.0))]
+    NullableRepetitions(Vec<NullableRepetitionError<'a>>),
+    #[error("{}", list(.0))]
+    NonproductiveRules(Vec<NonproductiveRuleError<'a>>),
+    #[error("{}", list(.0))]
+    MismatchedLiterals(Vec<MismatchedLiteralError>),
+}
+
+/// A piece repeated with `+` or `*` that can match nothing,
+/// so the repetition would never end
+#[derive(Debug, Error, PartialEq)]
+#[error("<{rule}> repeats {repeated}, which can match nothing")]
+pub struct NullableRepetitionError<'a> {
+    pub rule: &'a str,
+    pub repeated: String,
+}
+
+/// A rule that can't match any finite input, with the
+/// rules it depends on that can't either
+#[derive(Debug, Error, PartialEq)]
+#[error("<{rule}> can't match any finite input: {chain}")]
+pub struct NonproductiveRuleError<'a> {
+    pub rule: &'a str,
+    pub chain: String,
+}
+
+/// A literal that can't match in the grammar's mode: text
+/// that isn't ASCII with `@bytes`, since it could be encoded in
+/// more than one way, or a byte from 0x80 without it, since
+/// that's only part of a character in text
+#[derive(Debug, Error, PartialEq)]
+#[error("{place} has {literal}, which {}", mismatch(*.bytes))]
+pub struct MismatchedLiteralError {
+    /// the rule the literal is in, or the directive
+    pub place: String,
+    pub literal: String,
+    /// whether the grammar matches bytes
+    pub bytes: bool,
+}
+
+fn mismatch(bytes: bool) -> &'static str {
+    match bytes {
+        true => "isn't ASCII, and the grammar matches bytes",
+        false => "goes past 0x7F, and the grammar matches text",
+    }
+}
+
+fn list<T: ToString>(errors: &[T]) -> String {
+    let lines: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+    lines.join("\n")
+}
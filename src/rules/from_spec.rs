@@ -3,7 +3,23 @@ use std::collections::VecDeque;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
 
+use super::analysis::left_recursion::cycle_to_string;
+use super::analysis::left_recursion::left_recursive_cycles;
+use super::analysis::nullable::nullable_repetitions;
+use super::analysis::productive::nonproductive_rules;
+use super::analysis::reachability::lint;
+use super::analysis::reachability::LintReport;
+use super::choice::ChoiceStrategy;
+use super::error::GrammarError;
+use super::error::MismatchedLiteralError;
+use super::error::NonproductiveRuleError;
+use super::error::NullableRepetitionError;
 use super::grammar::Grammar;
+use super::literal::Literal;
+use super::literal::LiteralContent;
+use super::operators::Operator;
+use super::operators::OperatorKind;
+use super::operators::OperatorTable;
 use super::rule::Rule;
 use super::rule::RuleList;
 use super::rule::RuleOrs;
@@ -11,6 +27,8 @@ use super::rule::RulePiece;
 use super::rule::RulePieceContent;
 
 use crate::spec_parser::content::Content;
+use crate::spec_parser::directive::Directive as SpecDirective;
+use crate::spec_parser::directive::DirectiveArg as SpecDirectiveArg;
 use crate::spec_parser::grammar::Grammar as SpecGrammar;
 use crate::spec_parser::rule_line::RuleLine as SpecRuleLine;
 use crate::spec_parser::rule_ors::RuleOrs as SpecRuleOrs;
@@ -42,6 +60,7 @@ impl<'a> FromSpec<'a> for RulePiece<'a> {
             SpecRulePieceContent::Range(range) => {
                 RulePieceContent::from(RulePieceContent::from(range))
             }
+            SpecRulePieceContent::Bytes(range) => RulePieceContent::from(range),
         };
 
         let repetition = elem.repetition;
@@ -93,9 +112,24 @@ impl<'a> FromSpec<'a> for Rule<'a> {
 }
 
 impl<'a> TryFrom<&SpecGrammar<'a>> for Grammar<'a> {
-    type Error = String;
+    type Error = GrammarError<'a>;
 
     fn try_from(gram: &SpecGrammar<'a>) -> Result<Self, Self::Error> {
+        Self::from_linted(gram, &lint(gram))
+    }
+}
+
+impl<'a> Grammar<'a> {
+    /// Like `try_from`, for a grammar that was already linted
+    pub fn from_linted(
+        gram: &SpecGrammar<'a>,
+        report: &LintReport<'a>,
+    ) -> Result<Self, GrammarError<'a>> {
+        // report every missing rule, not only the first one we find
+        if report.has_errors() {
+            return Err(GrammarError::UndefinedRules(report.undefined.clone()));
+        }
+
         let mut rules = FxHashMap::default();
 
         let mut rules_to_check = VecDeque::with_capacity(gram.rules.len());
@@ -103,6 +137,75 @@ impl<'a> TryFrom<&SpecGrammar<'a>> for Grammar<'a> {
 
         let mut parsed = FxHashSet::default();
         rules_to_check.push_back(gram.main);
+        // the main rule is always the first one
+        id_gen.get(gram.main);
+
+        let mut skip_names = Vec::new();
+        let mut atomic = FxHashSet::default();
+        let mut operators = FxHashMap::default();
+        let mut keywords = FxHashSet::default();
+        let mut identifiers = FxHashSet::default();
+        let mut choice = None;
+        let mut choices = FxHashMap::default();
+        let mut sync = Vec::new();
+        let mut bytes = false;
+
+        for directive in &gram.directives {
+            match directive.name {
+                "skip" => {
+                    let names = directive_rules(directive)?;
+                    rules_to_check.extend(names.iter().copied());
+                    // a skip rule never skips inside of itself
+                    atomic.extend(names.iter().copied());
+                    skip_names.extend(names);
+                }
+                "atomic" => atomic.extend(directive_rules(directive)?),
+                "operators" => {
+                    let (name, table) = operator_table(directive, &mut id_gen)?;
+                    rules_to_check.extend(operator_rules(directive));
+
+                    if operators.insert(name, table).is_some() {
+                        return Err(GrammarError::InvalidDirective {
+                            directive: directive.name,
+                            message: format!("operators of <{}> declared twice", name),
+                        });
+                    }
+                }
+                "keywords" => {
+                    let (words, names) = keyword_args(directive)?;
+                    keywords.extend(words);
+                    rules_to_check.extend(names.iter().copied());
+                    // an identifier is a single token
+                    atomic.extend(names.iter().copied());
+                    identifiers.extend(names);
+                }
+                "choice" => {
+                    let (strategy, names) = choice_args(directive)?;
+                    let declared_twice = match names.is_empty() {
+                        true => choice.replace(strategy).is_some(),
+                        false => names
+                            .into_iter()
+                            .any(|name| choices.insert(name, strategy).is_some()),
+                    };
+
+                    if declared_twice {
+                        return Err(GrammarError::InvalidDirective {
+                            directive: directive.name,
+                            message: "strategy declared twice".into(),
+                        });
+                    }
+                }
+                "sync" => sync.extend(sync_tokens(directive)?),
+                "bytes" if directive.args.is_empty() => bytes = true,
+                "bytes" => {
+                    return Err(GrammarError::InvalidDirective {
+                        directive: directive.name,
+                        message: "expected no arguments".into(),
+                    })
+                }
+                other => return Err(GrammarError::UnknownDirective(other)),
+            }
+        }
 
         'checking_loop: loop {
             let line = match rules_to_check.pop_front() {
@@ -121,7 +224,7 @@ impl<'a> TryFrom<&SpecGrammar<'a>> for Grammar<'a> {
 
             let line = match gram.rules.get(line) {
                 Some(rule) => rule,
-                None => return Err(format!("can't find rule <{}>", line)),
+                None => unreachable!("undefined rules are reported by lint"),
             };
 
             let rule = Rule::from_spec(line, &mut id_gen);
@@ -139,8 +242,318 @@ impl<'a> TryFrom<&SpecGrammar<'a>> for Grammar<'a> {
             }
         }
 
-        Ok(Self { rules })
+        let skip = skip_names
+            .into_iter()
+            .map(|name| id_gen.get(name).0)
+            .collect();
+
+        let mut grammar = Self {
+            rules,
+            skip,
+            atomic,
+            operators,
+            keywords,
+            identifiers,
+            choice: choice.unwrap_or_default(),
+            choices,
+            sync,
+            bytes,
+            ..Default::default()
+        };
+
+        let mismatched = mismatched_literals(&grammar);
+        if !mismatched.is_empty() {
+            return Err(GrammarError::MismatchedLiterals(mismatched));
+        }
+
+        let nonproductive = nonproductive_rules(&grammar);
+        if !nonproductive.is_empty() {
+            let nonproductive = nonproductive
+                .into_iter()
+                .map(|found| NonproductiveRuleError {
+                    rule: grammar.rules[&found.rule].name,
+                    chain: cycle_to_string(&grammar, &found.chain),
+                })
+                .collect();
+
+            return Err(GrammarError::NonproductiveRules(nonproductive));
+        }
+
+        // these get parsed by growing a seed, see `Node::parse_rule`
+        let left_recursive: FxHashSet<_> = left_recursive_cycles(&grammar)
+            .into_iter()
+            .flatten()
+            .map(|id| grammar.rules[&id].name)
+            .collect();
+        grammar.left_recursive = left_recursive;
+
+        let repetitions = nullable_repetitions(&grammar);
+        if !repetitions.is_empty() {
+            let repetitions = repetitions
+                .into_iter()
+                .map(|rep| {
+                    let rule = &grammar.rules[&rep.rule];
+                    let piece = &rule.rule.0[rep.alternative].0[rep.piece];
+
+                    NullableRepetitionError {
+                        rule: rule.name,
+                        repeated: match &piece.content {
+                            RulePieceContent::Literal(literal) => format!("'{}'", literal),
+                            RulePieceContent::Rule(rf) => format!("<{}>", grammar.rules[rf].name),
+                        },
+                    }
+                })
+                .collect();
+
+            return Err(GrammarError::NullableRepetitions(repetitions));
+        }
+
+        Ok(grammar)
+    }
+}
+
+/// Compiles `@operators <rule> | left 1 '+' '-' | prefix 2 '-';`.
+/// The first group is the rule the operators belong to, every
+/// other group is a kind, a precedence and its operators
+fn operator_table<'a>(
+    directive: &SpecDirective<'a>,
+    id_gen: &mut Id<&'a str>,
+) -> Result<(&'a str, OperatorTable<'a>), GrammarError<'a>> {
+    let invalid = |message: String| GrammarError::InvalidDirective {
+        directive: directive.name,
+        message,
+    };
+
+    let groups = directive.groups();
+
+    let name = match groups[0] {
+        [SpecDirectiveArg::Piece(SpecRulePiece {
+            content: SpecRulePieceContent::Ident(ident),
+            ..
+        })] => ident.content(),
+        _ => return Err(invalid("expected the rule the operators belong to".into())),
+    };
+
+    let mut table = OperatorTable::default();
+
+    for group in &groups[1..] {
+        let (kind, precedence, pieces) = match group {
+            [SpecDirectiveArg::Word(kind), SpecDirectiveArg::Word(precedence), pieces @ ..] => {
+                (kind, precedence, pieces)
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "operators of <{}> must start with a kind and a precedence",
+                    name
+                )))
+            }
+        };
+
+        let kind = OperatorKind::from_word(kind).ok_or_else(|| {
+            invalid(format!(
+                "unknown operator kind {}, expected left, right, prefix or postfix",
+                kind
+            ))
+        })?;
+
+        let precedence: usize = precedence
+            .parse()
+            .map_err(|_| invalid(format!("invalid precedence {}", precedence)))?;
+
+        if pieces.is_empty() {
+            return Err(invalid(format!(
+                "no operators at precedence {}",
+                precedence
+            )));
+        }
+
+        for piece in pieces.iter() {
+            let piece = match piece {
+                SpecDirectiveArg::Piece(piece) => piece,
+                other => return Err(invalid(format!("expected an operator, found {}", other))),
+            };
+
+            table.0.push(Operator {
+                content: RulePiece::from_spec(piece, id_gen).content,
+                precedence,
+                kind,
+            });
+        }
     }
+
+    Ok((name, table))
+}
+
+/// Splits `@keywords <ident> 'if' 'while';` in the reserved
+/// words and the rules that can't match them
+fn keyword_args<'a>(
+    directive: &SpecDirective<'a>,
+) -> Result<(Vec<&'a str>, Vec<&'a str>), GrammarError<'a>> {
+    let invalid = |found: String| GrammarError::InvalidDirective {
+        directive: directive.name,
+        message: format!("expected rules and words, found {}", found),
+    };
+
+    let mut words = Vec::new();
+    let mut names = Vec::new();
+
+    for arg in &directive.args {
+        let piece = match arg {
+            SpecDirectiveArg::Piece(piece) => piece,
+            other => return Err(invalid(other.to_string())),
+        };
+
+        match &piece.content {
+            SpecRulePieceContent::Ident(ident) => names.push(ident.content()),
+            SpecRulePieceContent::Single(quote) => words.push(quote.content()),
+            SpecRulePieceContent::Double(quote) => words.push(quote.content()),
+            SpecRulePieceContent::Range(_) | SpecRulePieceContent::Bytes(_) => {
+                return Err(invalid(piece.to_string()))
+            }
+        }
+    }
+
+    Ok((words, names))
+}
+
+/// Splits `@choice first <a> <b>;` in the strategy and the rules
+/// it's used for. Without rules, it's used for the whole grammar
+fn choice_args<'a>(
+    directive: &SpecDirective<'a>,
+) -> Result<(ChoiceStrategy, Vec<&'a str>), GrammarError<'a>> {
+    let invalid = |message: String| GrammarError::InvalidDirective {
+        directive: directive.name,
+        message,
+    };
+
+    let strategy = match directive.args.first() {
+        Some(SpecDirectiveArg::Word(word)) => ChoiceStrategy::from_word(word).ok_or_else(|| {
+            invalid(format!(
+                "unknown strategy {}, expected longest, first or unambiguous",
+                word
+            ))
+        })?,
+        _ => return Err(invalid("expected a strategy".into())),
+    };
+
+    let names = directive.args[1..]
+        .iter()
+        .map(|arg| match arg {
+            SpecDirectiveArg::Piece(SpecRulePiece {
+                content: SpecRulePieceContent::Ident(ident),
+                ..
+            }) => Ok(ident.content()),
+            _ => Err(invalid(format!("expected rules, found {}", arg))),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((strategy, names))
+}
+
+/// The tokens of `@sync ';' '}';`
+fn sync_tokens<'a>(directive: &SpecDirective<'a>) -> Result<Vec<Literal<'a>>, GrammarError<'a>> {
+    directive
+        .args
+        .iter()
+        .map(|arg| match arg {
+            SpecDirectiveArg::Piece(piece) => match &piece.content {
+                SpecRulePieceContent::Single(quote) => Ok(quote.into()),
+                SpecRulePieceContent::Double(quote) => Ok(quote.into()),
+                SpecRulePieceContent::Range(range) => Ok(range.into()),
+                SpecRulePieceContent::Bytes(range) => Ok(range.into()),
+                SpecRulePieceContent::Ident(_) => Err(piece.to_string()),
+            },
+            other => Err(other.to_string()),
+        })
+        .map(|token| {
+            token.map_err(|found| GrammarError::InvalidDirective {
+                directive: directive.name,
+                message: format!("expected tokens, found {}", found),
+            })
+        })
+        .collect()
+}
+
+/// The literals that can't match in the mode of the grammar, in
+/// its rules, its operators, its keywords and its `@sync` tokens
+fn mismatched_literals(gram: &Grammar) -> Vec<MismatchedLiteralError> {
+    let mut rules: Vec<_> = gram.rules.iter().collect();
+    rules.sort_unstable_by_key(|(id, _)| **id);
+    let mut operators: Vec<_> = gram.operators.iter().collect();
+    operators.sort_unstable_by_key(|(name, _)| **name);
+    let mut keywords: Vec<_> = gram.keywords.iter().collect();
+    keywords.sort_unstable();
+
+    let mut literals = Vec::new();
+    for (_, rule) in rules {
+        let pieces = rule.rule.0.iter().flat_map(|list| &list.0);
+        for piece in pieces {
+            if let RulePieceContent::Literal(literal) = &piece.content {
+                literals.push((format!("<{}>", rule.name), literal.clone()));
+            }
+        }
+    }
+    for (name, table) in operators {
+        for op in &table.0 {
+            if let RulePieceContent::Literal(literal) = &op.content {
+                literals.push((format!("@operators <{}>", name), literal.clone()));
+            }
+        }
+    }
+    for word in keywords {
+        literals.push(("@keywords".into(), Literal::from(*word)));
+    }
+    for token in &gram.sync {
+        literals.push(("@sync".into(), token.clone()));
+    }
+
+    literals
+        .into_iter()
+        .filter(|(_, literal)| !literal.fits(gram.bytes))
+        .map(|(place, literal)| MismatchedLiteralError {
+            place,
+            literal: match literal.content {
+                LiteralContent::Str(string) => format!("'{}'", string),
+                LiteralContent::Range { .. } => literal.to_string(),
+            },
+            bytes: gram.bytes,
+        })
+        .collect()
+}
+
+/// The rules used as operators in an `@operators` directive
+fn operator_rules<'a>(directive: &SpecDirective<'a>) -> Vec<&'a str> {
+    directive
+        .args
+        .iter()
+        .skip(1)
+        .filter_map(|arg| match arg {
+            SpecDirectiveArg::Piece(SpecRulePiece {
+                content: SpecRulePieceContent::Ident(ident),
+                ..
+            }) => Some(ident.content()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the names of the rules a directive is applied to,
+/// failing if any of its arguments isn't a rule
+fn directive_rules<'a>(directive: &SpecDirective<'a>) -> Result<Vec<&'a str>, GrammarError<'a>> {
+    directive
+        .args
+        .iter()
+        .map(|arg| match arg {
+            SpecDirectiveArg::Piece(SpecRulePiece {
+                content: SpecRulePieceContent::Ident(ident),
+                ..
+            }) => Ok(ident.content()),
+            _ => Err(GrammarError::InvalidDirective {
+                directive: directive.name,
+                message: format!("expected rules, found {}", arg),
+            }),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -266,6 +679,39 @@ mod tests {
         assert_eq!(expected, got);
     }
 
+    #[test]
+    fn undefined_rules() {
+        let input = "<a>: <b> | <c>; <c>: <d>; @skip <e>;";
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let got = Grammar::try_from(&spec).unwrap_err();
+
+        let expected = [
+            "can't find rule <b>, used in <a> at line 1, column 6",
+            "can't find rule <d>, used in <c> at line 1, column 22",
+            "can't find rule <e>, used in @skip at line 1, column 33",
+        ];
+        assert_eq!(got.to_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn nullable_repetitions() {
+        let input = r#"
+<frase>: <parola> <spazio>* <parola> ''+;
+<spazio>: ' '+ | '';
+<parola>: [a-z]+;"#
+            .trim();
+
+        let (spec, _) = SpecGrammar::parse_len(input).unwrap();
+        let got = Grammar::try_from(&spec).unwrap_err();
+
+        let expected = [
+            "<frase> repeats <spazio>, which can match nothing",
+            "<frase> repeats '', which can match nothing",
+        ];
+        assert_eq!(got.to_string(), expected.join("\n"));
+    }
+
     // #[test]
     //     fn from_grammar() {
     //         let input = r#"
@@ -1,12 +1,40 @@
 use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
 
+use super::choice::ChoiceStrategy;
+use super::literal::Literal;
+use super::operators::OperatorTable;
 use super::rule::Rule;
 use super::rule::RuleRef;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Default)]
 pub struct Grammar<'a> {
     // todo: questo potrebbe tranquillamente essere un Vec
     pub rules: FxHashMap<RuleRef, Rule<'a>>,
+    /// rules declared with `@skip`, tried between the pieces
+    /// of every non-atomic rule
+    pub skip: Vec<RuleRef>,
+    /// rules declared with `@atomic`: nothing gets skipped inside
+    /// of them, or inside of the rules they call
+    pub atomic: FxHashSet<&'a str>,
+    /// operator tables declared with `@operators`, by rule name
+    pub operators: FxHashMap<&'a str, OperatorTable<'a>>,
+    /// words declared with `@keywords`. they only match
+    /// when they aren't followed by another word character
+    pub keywords: FxHashSet<&'a str>,
+    /// rules declared with `@keywords`, that can't match a keyword
+    pub identifiers: FxHashSet<&'a str>,
+    /// rules that can call themselves without consuming input
+    pub left_recursive: FxHashSet<&'a str>,
+    /// how rules pick an alternative, set with `@choice <strategy>;`
+    pub choice: ChoiceStrategy,
+    /// rules with their own strategy, set with `@choice <strategy> <rule>;`
+    pub choices: FxHashMap<&'a str, ChoiceStrategy>,
+    /// tokens declared with `@sync`, where parsing starts again
+    /// after an error in an item of a `*` repetition
+    pub sync: Vec<Literal<'a>>,
+    /// whether it matches bytes instead of text, set with `@bytes;`
+    pub bytes: bool,
 }
 
 impl<'a> Grammar<'a> {
@@ -17,4 +45,24 @@ impl<'a> Grammar<'a> {
     pub fn get(&'a self, rf: RuleRef) -> Option<&'a Rule<'a>> {
         self.rules.get(&rf)
     }
+
+    pub fn get_named(&'a self, name: &str) -> Option<&'a Rule<'a>> {
+        self.rules.values().find(|rule| rule.name == name)
+    }
+
+    pub fn is_atomic(&self, name: &str) -> bool {
+        self.atomic.contains(name)
+    }
+
+    pub fn is_left_recursive(&self, name: &str) -> bool {
+        self.left_recursive.contains(name)
+    }
+
+    pub fn choice(&self, name: &str) -> ChoiceStrategy {
+        self.choices.get(name).copied().unwrap_or(self.choice)
+    }
+
+    pub fn is_keyword(&self, word: &str) -> bool {
+        self.keywords.contains(word)
+    }
 }
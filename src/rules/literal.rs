@@ -1,20 +1,21 @@
 use std::fmt;
-use std::str::Chars;
 
 use super::rule::RulePiece;
 use super::rule::RulePieceContent;
+use crate::spec_parser::byte_range::ByteRange;
 use crate::spec_parser::char_range::CharRange;
 use crate::spec_parser::content::Content;
 use crate::spec_parser::rule_piece::Repetition;
-use crate::spec_parser::strings::trim_end;
-use crate::spec_parser::strings::trim_start;
 use crate::spec_parser::tokens::DoubleQuote;
 use crate::spec_parser::tokens::SingleQuote;
+use crate::utils::first_char;
 
 // todo: non serve più il content
 #[derive(Debug, PartialEq, Clone)]
 pub struct Literal<'a> {
     pub content: LiteralContent<'a>,
+    /// whether it's a range of bytes, like `0x00-0x1F`
+    pub bytes: bool,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -29,55 +30,126 @@ impl<'a> fmt::Display for Literal<'a> {
 
         match self.content {
             LiteralContent::Str(string) => write!(f, "{}", string),
+            // see `ByteRange`
+            LiteralContent::Range { from, to } if self.bytes => {
+                write!(f, "0x{:02X}-0x{:02X}", from as u32, to as u32)
+            }
             LiteralContent::Range { from, to } => write!(f, "[{}-{}]", from, to),
         }
     }
 }
 
 impl<'a> Literal<'a> {
-    pub fn match_str<'b>(&self, input: &'b str, repeated: bool) -> Option<&'b str> {
-        // todo: a little bit ugly
+    /// How many bytes at the start of `input` it matches. Byte ranges
+    /// match single bytes, everything else matches UTF-8 text
+    pub fn match_bytes(&self, input: &[u8], repeated: bool) -> Option<usize> {
         match self.content {
             LiteralContent::Range { from, to } => {
-                let mut chars: Chars<'b> = input.chars();
-                if let Some(rune) = chars.next() {
-                    if !in_range(rune, from, to) {
-                        return None;
+                let mut len = 0;
+                loop {
+                    match self.range_len(&input[len..], from, to) {
+                        Some(matched) => len += matched,
+                        // the end of the input doesn't fail a range
+                        None if len == 0 && !input.is_empty() => return None,
+                        None => return Some(len),
                     }
-                }
 
-                let mut rest = chars.as_str();
-                if repeated {
-                    while let Some(rune) = chars.next() {
-                        if !in_range(rune, from, to) {
-                            break;
-                        } else {
-                            rest = chars.as_str();
-                        }
+                    if !repeated {
+                        return Some(len);
                     }
                 }
-                return Some(trim_end(input, rest));
             }
             LiteralContent::Str(string) => {
-                let mut left: &'b str = trim_start(input, string)?;
-                if !repeated {
-                    return Some(trim_end(input, left));
-                }
+                let mut len = 0;
+                while input[len..].starts_with(string.as_bytes()) {
+                    len += string.len();
 
-                loop {
-                    left = match trim_start(left, string) {
-                        Some(left) => left,
-                        None => return Some(trim_end(input, left)),
-                    };
+                    // an empty string would be repeated forever
+                    if !repeated || string.is_empty() {
+                        break;
+                    }
                 }
+
+                (len > 0 || string.is_empty()).then_some(len)
             }
         }
     }
+
+    /// Like `match_bytes`, for text
+    pub fn match_str<'b>(&self, input: &'b str, repeated: bool) -> Option<&'b str> {
+        self.match_bytes(input.as_bytes(), repeated)
+            .map(|len| &input[..len])
+    }
+
+    /// How long the byte or the character at the start of
+    /// `input` is, if it's in the range
+    fn range_len(&self, input: &[u8], from: char, to: char) -> Option<usize> {
+        match self.bytes {
+            true => input
+                .first()
+                .filter(|&&byte| in_range(char::from(byte), from, to))
+                .map(|_| 1),
+            false => first_char(input)
+                .filter(|&rune| in_range(rune, from, to))
+                .map(char::len_utf8),
+        }
+    }
+
+    /// Whether it can match in a grammar that matches bytes, or one
+    /// that matches text, see `MismatchedLiteralError`
+    pub fn fits(&self, bytes: bool) -> bool {
+        match (&self.content, bytes) {
+            (LiteralContent::Str(string), true) => string.is_ascii(),
+            (LiteralContent::Str(_), false) => true,
+            (LiteralContent::Range { to, .. }, bytes) => self.bytes == bytes || to.is_ascii(),
+        }
+    }
+}
+
+impl<'a> Literal<'a> {
+    /// Whether matching at the start of `input` could go
+    /// differently if there was more text after it
+    pub fn needs_more(&self, input: &[u8], repeated: bool) -> bool {
+        let matched = self.match_bytes(input, repeated);
+
+        match self.content {
+            LiteralContent::Range { .. } => match repeated {
+                true => matched == Some(input.len()),
+                false => input.is_empty(),
+            },
+            LiteralContent::Str(string) => {
+                let rest = match matched {
+                    Some(matched) if repeated => &input[matched..],
+                    Some(_) => return false,
+                    None => input,
+                };
+
+                !string.is_empty() && string.as_bytes().starts_with(rest)
+            }
+        }
+    }
+
+    /// How many bytes at the start of the input matching looked at,
+    /// given how many it `matched`: those, and what it tried after
+    /// them, which can go past the end of the input
+    pub fn looks_at(&self, matched: Option<usize>, repeated: bool) -> usize {
+        let tried = match (&self.content, matched) {
+            (_, Some(_)) if !repeated => 0,
+            (LiteralContent::Str(string), _) => string.len(),
+            // the start of a character is enough to tell
+            (LiteralContent::Range { .. }, _) => 1,
+        };
+
+        matched.unwrap_or(0) + tried
+    }
 }
 
 impl<'a> From<LiteralContent<'a>> for Literal<'a> {
     fn from(content: LiteralContent<'a>) -> Self {
-        Self { content: content }
+        Self {
+            content: content,
+            bytes: false,
+        }
     }
 }
 
@@ -89,6 +161,7 @@ impl<'a> From<&'a str> for Literal<'a> {
     fn from(string: &'a str) -> Self {
         Self {
             content: LiteralContent::Str(string),
+            bytes: false,
         }
     }
 }
@@ -133,10 +206,30 @@ impl<'a> From<&CharRange> for Literal<'a> {
     fn from(range: &CharRange) -> Self {
         Self {
             content: range.into(),
+            bytes: false,
+        }
+    }
+}
+
+/// Bytes are kept as the characters with the same codes
+impl<'a> From<&ByteRange> for Literal<'a> {
+    fn from(range: &ByteRange) -> Self {
+        Self {
+            content: LiteralContent::Range {
+                from: char::from(range.from),
+                to: char::from(range.to),
+            },
+            bytes: true,
         }
     }
 }
 
+impl<'a> From<&ByteRange> for RulePieceContent<'a> {
+    fn from(range: &ByteRange) -> Self {
+        Self::Literal(range.into())
+    }
+}
+
 impl<'a> From<&SingleQuote<'a>> for RulePieceContent<'a> {
     fn from(quote: &SingleQuote<'a>) -> Self {
         Self::Literal(quote.into())
@@ -1,4 +1,8 @@
+pub mod analysis;
+pub mod choice;
+pub mod error;
 pub mod from_spec;
 pub mod grammar;
 pub mod literal;
+pub mod operators;
 pub mod rule;
@@ -0,0 +1,52 @@
+use super::rule::RulePieceContent;
+
+/// The operators of a rule declared with `@operators`.
+/// The rule's own alternatives are its operands, and
+/// the operators are combined with precedence climbing
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct OperatorTable<'a>(pub Vec<Operator<'a>>);
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Operator<'a> {
+    pub content: RulePieceContent<'a>,
+    /// higher binds tighter
+    pub precedence: usize,
+    pub kind: OperatorKind,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum OperatorKind {
+    Binary(Associativity),
+    Prefix,
+    Postfix,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+impl<'a> OperatorTable<'a> {
+    pub fn prefix(&self) -> impl Iterator<Item = &Operator<'a>> {
+        self.0.iter().filter(|op| op.kind == OperatorKind::Prefix)
+    }
+
+    /// The binary and postfix operators, the ones that
+    /// come after an operand
+    pub fn infix(&self) -> impl Iterator<Item = &Operator<'a>> {
+        self.0.iter().filter(|op| op.kind != OperatorKind::Prefix)
+    }
+}
+
+impl OperatorKind {
+    pub fn from_word(word: &str) -> Option<Self> {
+        match word {
+            "left" => Some(Self::Binary(Associativity::Left)),
+            "right" => Some(Self::Binary(Associativity::Right)),
+            "prefix" => Some(Self::Prefix),
+            "postfix" => Some(Self::Postfix),
+            _ => None,
+        }
+    }
+}
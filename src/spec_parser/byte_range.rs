@@ -0,0 +1,82 @@
+use std::fmt;
+
+use crate::utils::take_n;
+
+use super::error::ParseError;
+use super::node::Node;
+
+pub const BYTE_PREFIX: &str = "0x";
+pub const BYTE_SEPARATOR: char = '-';
+
+/// A byte like `0x0A`, or a range of bytes like `0x00-0x1F`.
+/// When parsing text it matches the characters with those codes
+#[derive(Debug, PartialEq)]
+pub struct ByteRange {
+    pub from: u8,
+    pub to: u8,
+}
+
+impl<'a> Node<'a> for ByteRange {
+    fn parse_len(input: &'a str) -> Result<(Self, usize), ParseError<'a>> {
+        let expected = |rest: &'a str| ParseError::ExpectedWhile {
+            parsing: "byte range",
+            expected: "byte like 0x1F",
+            found: take_n(rest, 20),
+            line: 0,
+        };
+
+        let (from, mut rest) = byte(input).ok_or_else(|| expected(input))?;
+        let mut to = from;
+
+        if let Some(after) = rest.strip_prefix(BYTE_SEPARATOR) {
+            (to, rest) = byte(after).ok_or_else(|| expected(after))?;
+        }
+
+        Ok((Self { from, to }, input.len() - rest.len()))
+    }
+}
+
+/// A byte written in hex, and the input after it
+fn byte(input: &str) -> Option<(u8, &str)> {
+    let digits = input.strip_prefix(BYTE_PREFIX)?;
+    let hex = digits.get(..2)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some((u8::from_str_radix(hex, 16).ok()?, &digits[2..]))
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.from == self.to {
+            true => write!(f, "0x{:02X}", self.from),
+            false => write!(f, "0x{:02X}-0x{:02X}", self.from, self.to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let (range, rest) = ByteRange::parse_and_skip("0x00-0x1f+").unwrap();
+        assert_eq!(range, ByteRange { from: 0, to: 0x1F });
+        assert_eq!(rest, "+");
+
+        let (byte, rest) = ByteRange::parse_and_skip("0x89 'PNG'").unwrap();
+        assert_eq!(
+            byte,
+            ByteRange {
+                from: 0x89,
+                to: 0x89
+            }
+        );
+        assert_eq!(rest, " 'PNG'");
+
+        assert!(ByteRange::parse_len("0x8").is_err());
+        assert!(ByteRange::parse_len("0x00-").is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::take_start;
+use crate::utils::take_n;
+
+use super::error::ParseError;
+use super::node::Node;
+use super::rule_piece::RulePiece;
+use super::tokens::PrimitiveNode;
+use super::tokens::{Space, SEMICOLUMN, SEPARATOR};
+
+pub const START_DIRECTIVE: char = '@';
+
+/// A grammar-level declaration, like `@skip <spazio>;`.
+/// Directives don't define rules, they change how the
+/// grammar gets compiled or parsed
+#[derive(Debug, PartialEq)]
+pub struct Directive<'a> {
+    pub name: &'a str,
+    pub args: Vec<DirectiveArg<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DirectiveArg<'a> {
+    Piece(RulePiece<'a>),
+    /// a bare word or number, like `left` or `3`
+    Word(&'a str),
+    Separator,
+}
+
+impl<'a> Directive<'a> {
+    /// Splits the arguments on `|`
+    pub fn groups(&self) -> Vec<&[DirectiveArg<'a>]> {
+        self.args
+            .split(|arg| *arg == DirectiveArg::Separator)
+            .collect()
+    }
+}
+
+impl<'a> Node<'a> for Directive<'a> {
+    fn parse_len(input: &'a str) -> Result<(Self, usize), ParseError<'a>> {
+        let rest = match Space::parse_and_skip(input) {
+            Some((_, rest)) => rest,
+            None => input,
+        };
+
+        let rest = rest
+            .strip_prefix(START_DIRECTIVE)
+            .ok_or(ParseError::ExpectedWhile {
+                parsing: "directive",
+                expected: "@",
+                found: take_n(rest, 20),
+                line: 0,
+            })?;
+
+        let name = take_word(rest).ok_or(ParseError::ExpectedWhile {
+            parsing: "directive",
+            expected: "directive name",
+            found: take_n(rest, 20),
+            line: 0,
+        })?;
+        let mut rest = &rest[name.len()..];
+        let mut args = Vec::new();
+
+        'parse_loop: loop {
+            rest = match Space::parse_and_skip(rest) {
+                Some((_, rest)) => rest,
+                None => rest,
+            };
+
+            if rest.starts_with(SEMICOLUMN) {
+                break 'parse_loop;
+            }
+
+            let (arg, len) = if let Ok((piece, len)) = RulePiece::parse_len(rest) {
+                (DirectiveArg::Piece(piece), len)
+            } else if rest.starts_with(SEPARATOR) {
+                (DirectiveArg::Separator, SEPARATOR.len_utf8())
+            } else if let Some(word) = take_word(rest) {
+                (DirectiveArg::Word(word), word.len())
+            } else {
+                return Err(ParseError::ExpectedWhile {
+                    parsing: "directive",
+                    expected: "semicolon",
+                    found: take_n(rest, 20),
+                    line: 0,
+                });
+            };
+
+            args.push(arg);
+            rest = &rest[len..];
+        }
+
+        let rest = &rest[SEMICOLUMN.len_utf8()..];
+        let diff = input.len() - rest.len();
+        Ok((Self { name, args }, diff))
+    }
+}
+
+fn take_word(input: &str) -> Option<&str> {
+    take_start!(input, |c: char| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rule_piece::RulePieceContent;
+    use super::super::tokens::{Identifier, SingleQuote};
+    use super::*;
+
+    #[test]
+    fn directive() {
+        let input = "\n@skip <spazio> <commento>;\n<oggetto>: '{' '}';";
+        let expected = Directive {
+            name: "skip",
+            args: vec![
+                DirectiveArg::Piece(RulePieceContent::from(Identifier("<spazio>")).into()),
+                DirectiveArg::Piece(RulePieceContent::from(Identifier("<commento>")).into()),
+            ],
+        };
+
+        let (got, left) = Directive::parse_and_skip(input).unwrap();
+        assert_eq!(expected, got);
+        assert_eq!(left, "\n<oggetto>: '{' '}';");
+    }
+
+    #[test]
+    fn groups() {
+        let input = "@operators <espressione> | left 1 '+' '-' | prefix 2 '-';";
+        let (got, _) = Directive::parse_len(input).unwrap();
+
+        let groups = got.groups();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            groups[1],
+            &[
+                DirectiveArg::Word("left"),
+                DirectiveArg::Word("1"),
+                DirectiveArg::Piece(RulePieceContent::from(SingleQuote("'+'")).into()),
+                DirectiveArg::Piece(RulePieceContent::from(SingleQuote("'-'")).into()),
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use super::directive::Directive;
+use super::directive::DirectiveArg;
 use super::grammar::Grammar;
 use super::rule_line::RuleLine;
 use super::rule_ors::RuleOrs;
@@ -14,6 +16,7 @@ impl<'a> fmt::Display for RulePiece<'a> {
             RulePieceContent::Single(content) => write!(f, "{}", content.0),
             RulePieceContent::Ident(content) => write!(f, "{}", content.0),
             RulePieceContent::Range(range) => write!(f, "{}", range),
+            RulePieceContent::Bytes(range) => write!(f, "{}", range),
         }
     }
 }
@@ -59,8 +62,34 @@ impl<'a> fmt::Display for RuleLine<'a> {
     }
 }
 
+impl<'a> fmt::Display for DirectiveArg<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DirectiveArg::Piece(piece) => write!(f, "{}", piece),
+            DirectiveArg::Word(word) => write!(f, "{}", word),
+            DirectiveArg::Separator => write!(f, "|"),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Directive<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.name)?;
+
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<'a> fmt::Display for Grammar<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for directive in &self.directives {
+            writeln!(f, "{};", directive)?;
+        }
+
         let mut rules = self.rules.iter();
         write!(f, "{};", rules.next().unwrap().1)?;
 
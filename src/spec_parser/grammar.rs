@@ -1,5 +1,6 @@
 use rustc_hash::FxHashMap;
 
+use crate::spec_parser::directive::Directive;
 use crate::spec_parser::error::ParseError;
 use crate::spec_parser::node::Node;
 use crate::spec_parser::rule_line::RuleLine;
@@ -8,15 +9,25 @@ use crate::spec_parser::rule_line::RuleLine;
 pub struct Grammar<'a> {
     pub main: &'a str,
     pub rules: FxHashMap<&'a str, RuleLine<'a>>,
+    pub directives: Vec<Directive<'a>>,
+    /// the text the grammar was parsed from
+    pub source: &'a str,
 }
 
 impl<'a> Node<'a> for Grammar<'a> {
     fn parse_len(input: &'a str) -> Result<(Self, usize), ParseError> {
         let mut rules = FxHashMap::default();
+        let mut directives = Vec::new();
         let mut first_rule = None;
 
         let mut left = input;
         'parse_loop: loop {
+            if let Ok((directive, trim)) = Directive::parse_and_skip(left) {
+                left = trim;
+                directives.push(directive);
+                continue 'parse_loop;
+            }
+
             let rule = match RuleLine::parse_and_skip(left) {
                 Ok((rule, trim)) => {
                     left = trim;
@@ -42,6 +53,8 @@ impl<'a> Node<'a> for Grammar<'a> {
                 Self {
                     main: first_rule.unwrap(),
                     rules: rules,
+                    directives,
+                    source: input,
                 },
                 diff,
             )),
@@ -15,6 +15,7 @@ impl<'a> IsPrimitive for RulePiece<'a> {
             RulePieceContent::Single(_) => true,
             RulePieceContent::Ident(_) => false,
             RulePieceContent::Range(_) => true,
+            RulePieceContent::Bytes(_) => true,
         }
     }
 }
@@ -1,5 +1,7 @@
+pub mod byte_range;
 pub mod char_range;
 pub mod content;
+pub mod directive;
 pub mod error;
 pub mod format;
 pub mod grammar;
@@ -1,5 +1,6 @@
 use crate::utils::take_n;
 
+use super::byte_range::ByteRange;
 use super::char_range::CharRange;
 use super::error::ParseError;
 use super::node::Node;
@@ -17,6 +18,7 @@ pub enum RulePieceContent<'a> {
     Double(DoubleQuote<'a>),
     Ident(Identifier<'a>),
     Range(CharRange),
+    Bytes(ByteRange),
 }
 
 pub const REPEAT_TOGETHER: char = '+';
@@ -39,6 +41,8 @@ impl<'a> Node<'a> for RulePiece<'a> {
             (RulePieceContent::Ident(parsed), len)
         } else if let Ok((parsed, len)) = CharRange::parse_len(input) {
             (RulePieceContent::Range(parsed), len)
+        } else if let Ok((parsed, len)) = ByteRange::parse_len(input) {
+            (RulePieceContent::Bytes(parsed), len)
         } else {
             return Err(ParseError::ExpectedWhile {
                 parsing: "rule piece",
@@ -102,6 +106,12 @@ impl<'a> From<CharRange> for RulePieceContent<'a> {
     }
 }
 
+impl<'a> From<ByteRange> for RulePieceContent<'a> {
+    fn from(range: ByteRange) -> Self {
+        Self::Bytes(range)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
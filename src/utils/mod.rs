@@ -1,3 +1,5 @@
+use serde::Serialize;
+
 pub fn take_n<'a>(input: &'a str, n: usize) -> &'a str {
     let end = {
         let mut chars = input.chars();
@@ -9,6 +11,122 @@ pub fn take_n<'a>(input: &'a str, n: usize) -> &'a str {
     &input[..bytes]
 }
 
+/// The longest beginning of `bytes` that's UTF-8
+pub fn utf8_prefix(bytes: &[u8]) -> &str {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => std::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap(),
+    }
+}
+
+/// The character at the start of `bytes`, if they start with one
+pub fn first_char(bytes: &[u8]) -> Option<char> {
+    // a character is at most 4 bytes
+    utf8_prefix(&bytes[..bytes.len().min(4)]).chars().next()
+}
+
+/// Whether a character can be part of a word,
+/// and so of an identifier or a keyword
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// A line and a column in some text, both starting from 1.
+/// Columns count characters, not bytes
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of the byte at `offset`
+    pub fn from_offset(text: &str, offset: usize) -> Self {
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// The position where `slice` begins. `slice` must
+    /// be a piece of `text`
+    pub fn of_slice(text: &str, slice: &str) -> Self {
+        let offset = slice.as_ptr() as usize - text.as_ptr() as usize;
+        Self::from_offset(text, offset)
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// The positions of many offsets at once, going through `text`
+/// only one time. `offsets` must be sorted
+pub fn positions(text: &str, offsets: &[usize]) -> Vec<Position> {
+    positions_after(text, (0, Position { line: 1, column: 1 }), offsets)
+}
+
+/// Like `positions`, but starts counting from `known`, an offset
+/// whose position is known already. The offsets must come after it
+pub fn positions_after(text: &str, known: (usize, Position), offsets: &[usize]) -> Vec<Position> {
+    let (start, mut position) = known;
+    let mut found = Vec::with_capacity(offsets.len());
+    let mut chars = text[start..]
+        .char_indices()
+        .map(|(i, c)| (start + i, c))
+        .peekable();
+
+    for &offset in offsets {
+        while let Some((_, c)) = chars.next_if(|(i, _)| *i < offset) {
+            match c {
+                '\n' => position = Position { line: position.line + 1, column: 1 },
+                _ => position.column += 1,
+            }
+        }
+
+        found.push(position);
+    }
+
+    found
+}
+
+/// The line of `text` that contains `offset`, with a
+/// caret under it on the line after
+pub fn snippet(text: &str, offset: usize) -> String {
+    let position = Position::from_offset(text, offset);
+    let line = text.lines().nth(position.line - 1).unwrap_or("");
+
+    format!("{}\n{}^", line, " ".repeat(position.column - 1))
+}
+
+/// The Levenshtein distance: how many characters must be
+/// inserted, removed or changed to turn one string in the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let changed = previous[j] + if ca == *cb { 0 } else { 1 };
+            let removed = previous[j + 1] + 1;
+            let inserted = current[j] + 1;
+
+            current.push(changed.min(removed).min(inserted));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 #[allow(dead_code)]
 pub fn match_start<'a>(input: &'a str, matcher: &str) -> Option<&'a str> {
     if input.starts_with(matcher) {
@@ -17,3 +135,41 @@ pub fn match_start<'a>(input: &'a str, matcher: &str) -> Option<&'a str> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        let cases = [
+            ("", "", 0),
+            ("spazio", "spazio", 0),
+            ("spazo", "spazio", 1),
+            ("sapzio", "spazio", 2),
+            ("numero", "nome", 3),
+            ("", "abc", 3),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(edit_distance(a, b), expected);
+            assert_eq!(edit_distance(b, a), expected);
+        }
+    }
+
+    #[test]
+    fn position() {
+        let text = "<a>: 'x';\n<b>: <à> <c>;";
+
+        assert_eq!(Position::from_offset(text, 0), Position { line: 1, column: 1 });
+        assert_eq!(Position::of_slice(text, &text[15..]), Position { line: 2, column: 6 });
+        assert_eq!(Position::of_slice(text, &text[20..]), Position { line: 2, column: 10 });
+
+        let offsets = [0, 15, 20, text.len()];
+        let expected: Vec<_> = offsets
+            .iter()
+            .map(|&offset| Position::from_offset(text, offset))
+            .collect();
+        assert_eq!(positions(text, &offsets), expected);
+    }
+}